@@ -2,16 +2,30 @@
 
 use crate::proto::Protocol;
 use crate::{unsafe_guid, Result, Status};
+use core::convert::TryFrom;
 use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem::size_of;
 use core::ptr;
+use core::slice;
+use core::str;
+use core::time::Duration;
 use num_enum::{
     TryFromPrimitive,
+    TryFromPrimitiveError,
     IntoPrimitive,
 };
 use alloc_api::{
     format,
     string::String,
 };
+#[cfg(feature = "exts")]
+use crate::table::boot::{BootServices, MemoryType};
+#[cfg(feature = "exts")]
+use alloc_api::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+
+pub mod options;
+pub use self::options::OptionIterator;
 
 /// The DHCP4 protocol is used to collect configuration information for the EFI
 /// IPv4 Protocol driver and provide DHCP server and PXE boot server directory
@@ -35,11 +49,23 @@ pub struct DHCP4 {
         event: *mut crate::Event,
     ) -> Status,
 
-    renew_rebind: usize,
-    release: usize,
-    stop: usize,
+    renew_rebind: extern "efiapi" fn(
+        this: &mut DHCP4,
+        rebind_request: bool,
+        completion_event: *mut c_void,
+    ) -> Status,
+
+    release: extern "efiapi" fn(this: &mut DHCP4) -> Status,
+
+    stop: extern "efiapi" fn(this: &mut DHCP4) -> Status,
+
     build: usize,
-    transmit_receive: usize,
+
+    transmit_receive: extern "efiapi" fn(
+        this: &mut DHCP4,
+        token: *mut TransmitReceiveToken,
+    ) -> Status,
+
     parse: usize,
 }
 
@@ -54,6 +80,15 @@ impl DHCP4 {
 
     }
 
+    /// Returns a copy of the current operating mode and cached data packet
+    /// for the EFI DHCPv4 Protocol driver.
+    pub fn mode_data(&mut self) -> Result<ModeData> {
+
+        let mut mode_data = ModeData::default();
+        (self.get_mode_data)(self, &mut mode_data).into_with_val(|| mode_data)
+
+    }
+
     /// Initializes, changes, or resets the operational settings for the EFI
     /// DHCPv4 Protocol driver.
     pub fn configure(
@@ -71,10 +106,252 @@ impl DHCP4 {
 
     }
 
+    /// Configures the driver and runs the DHCP process to completion,
+    /// returning the resulting mode data.
+    pub fn acquire(&mut self, config: &ConfigData) -> Result<ModeData> {
+
+        self.configure(config)?.log();
+        self.start(ptr::null_mut())?.log();
+        self.mode_data()
+
+    }
+
+    /// Fetches the configuration parameters, e.g. the DNS servers, of a
+    /// client already using `client_address`, such as a statically assigned
+    /// address, by broadcasting a DHCPINFORM.
+    ///
+    /// No lease is obtained: the driver is reset and configured with
+    /// `client_address`, but not started, so it is left in the
+    /// Dhcp4InitReboot state rather than bound. The reply packet of the
+    /// returned mode data is the first DHCPACK received, copied to pool
+    /// memory which the caller frees with `free_pool`.
+    ///
+    /// Fails with `INVALID_PARAMETER` if `client_address` is unspecified, and
+    /// with `NO_RESPONSE` if no server acknowledges the message.
+    #[cfg(feature = "exts")]
+    pub fn inform(&mut self, client_address: Ipv4Addr, bt: &BootServices) -> Result<ModeData> {
+
+        if client_address.is_unspecified() {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+
+        // the driver only takes the address when configured from stopped
+        self.configure(ptr::null())?.log();
+        let config = ConfigDataBuilder::new()
+            .client_address(client_address)
+            .build();
+        self.configure(&*config)?.log();
+
+        let mut mode_data = self.mode_data()?.log();
+        mode_data.client_address = client_address.into();
+        let mut buffer = mode_data.client_message(options::DHCPINFORM, &[]);
+
+        let mut token = TransmitReceiveToken{
+            remote_address: Ipv4Addr::new(255, 255, 255, 255).into(),
+            remote_port: SERVER_PORT,
+            timeout_value: 4,
+            packet: buffer.as_mut_ptr() as *const Packet,
+            ..TransmitReceiveToken::default()
+        };
+        let sent = match self.transmit_receive(&mut token) {
+            Err(err) if err.status() == Status::TIMEOUT => Err(Status::NO_RESPONSE.into()),
+            sent => sent,
+        };
+
+        let reply: Result<*mut u8> = sent.and_then(|_| {
+            let ack = token.response(options::DHCPACK).ok_or(Status::NO_RESPONSE)?;
+            let size = ack.size as usize;
+            let copy = bt.allocate_pool(MemoryType::LOADER_DATA, size)?.log();
+            unsafe { ptr::copy_nonoverlapping(ack as *const Packet as *const u8, copy, size) };
+            Ok(copy.into())
+        });
+        if !token.response_list.is_null() {
+            bt.free_pool(token.response_list as *mut u8)?.log();
+        }
+
+        mode_data.reply_packet = reply?.log() as *const Packet;
+        Ok(mode_data.into())
+
+    }
+
+    /// Returns true if the driver has been configured, and so may be started
+    /// without calling `configure` again.
+    pub fn is_configured(&mut self) -> Result<bool> {
+
+        let mode_data = self.mode_data()?.log();
+        Ok(mode_data.is_configured().into())
+
+    }
+
+    /// Runs the DHCP process like `acquire`, recording every event of the
+    /// negotiation along with a copy of its packet.
+    ///
+    /// The callback of `config` is replaced for the duration of the
+    /// negotiation, and the driver is left configured without a callback.
+    #[cfg(feature = "exts")]
+    pub fn capture_negotiation(&mut self, config: &ConfigData) -> Result<NegotiationLog> {
+
+        let mut entries = Vec::new();
+
+        let mut capture = CallbackConfig::new(*config);
+        capture.set_callback(|state, event, packet| {
+            entries.push(NegotiationEntry{
+                state,
+                event,
+                packet: packet.map(Packet::to_vec),
+            });
+            CallbackAction::Continue
+        });
+
+        let result = self.acquire(capture.config());
+
+        // The driver must not call back into the closure once it is dropped
+        let plain = ConfigData{
+            dhcp4_callback: None,
+            callback_context: ptr::null_mut(),
+            ..*config
+        };
+        let reset = self.configure(&plain);
+        drop(capture);
+
+        let mode_data = result?.log();
+        reset?.log();
+        Ok(NegotiationLog{ entries, mode_data }.into())
+
+    }
+
+    /// Replaces the closure of `config`, the configuration the driver is
+    /// running with, and reconfigures the driver in place, keeping its lease.
+    ///
+    /// The driver copies the timeouts and options it is configured with, and
+    /// frees those copies when reconfigured, so the configuration reported by
+    /// `mode_data` cannot be reused: `config` must be the one last passed to
+    /// `configure`. Fails with `INVALID_PARAMETER` if the driver reports
+    /// another callback context.
+    ///
+    /// Reconfiguring is only valid while stopped, initializing or bound; in
+    /// the selecting, requesting, renewing, rebinding and rebooting states
+    /// this fails with `ACCESS_DENIED` without calling the driver. On failure
+    /// `config` keeps its previous closure.
+    #[cfg(feature = "exts")]
+    pub fn update_callback<'a>(
+        &mut self,
+        config: &mut CallbackConfig<'a>,
+        f: impl FnMut(State, Event, Option<&Packet>) -> CallbackAction + 'a,
+    ) -> Result<()> {
+
+        let mode_data = self.mode_data()?.log();
+        match mode_data.state() {
+            Ok(State::Dhcp4Stopped)
+            | Ok(State::Dhcp4Init)
+            | Ok(State::Dhcp4InitReboot)
+            | Ok(State::Dhcp4Bound) => {}
+            _ => return Err(Status::ACCESS_DENIED.into()),
+        }
+        if mode_data.config_data.callback_context != config.config.callback_context {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+
+        // The driver may call the previous closure until it is reconfigured,
+        // and keeps doing so if reconfiguring fails
+        let previous = (config.config, config.handler.take());
+        config.set_callback(f);
+
+        let status = self.configure(config.config());
+        if status.is_err() {
+            config.config = previous.0;
+            config.handler = previous.1;
+        }
+        status
+
+    }
+
+    /// Extends the lease time, blocking until the renewal completes. A
+    /// rebind broadcasts the request instead of sending it to the server
+    /// that granted the lease.
+    pub fn renew_rebind(&mut self, rebind: bool) -> Result<()> {
+
+        (self.renew_rebind)(self, rebind, ptr::null_mut()).into()
+
+    }
+
+    /// Releases the current address configuration.
+    pub fn release(&mut self) -> Result<()> {
+
+        (self.release)(self).into()
+
+    }
+
+    /// Stops the configuration process, dropping any lease without
+    /// notifying the server.
+    pub fn stop(&mut self) -> Result<()> {
+
+        (self.stop)(self).into()
+
+    }
+
+    /// Transmits a DHCP packet and collects the responses, as described by
+    /// `token`.
+    ///
+    /// Without a completion event this blocks until the timeout of the token
+    /// expires. The response list is allocated by the driver and must be
+    /// freed by the caller.
+    pub fn transmit_receive(&mut self, token: &mut TransmitReceiveToken) -> Result<()> {
+
+        (self.transmit_receive)(self, token).into()
+
+    }
+
+    /// Releases the current address configuration to the server that granted
+    /// it.
+    ///
+    /// The DHCPRELEASE is unicast to the server identified by `server_id`, so
+    /// that on a segment with several DHCP servers it reaches the one holding
+    /// the lease. The driver is then stopped, dropping the lease locally
+    /// without sending a release of its own. Fails with `NOT_STARTED` if
+    /// there is no lease.
+    ///
+    /// The boot services free the responses the driver collects while
+    /// sending the message, which it allocates from pool memory.
+    #[cfg(feature = "exts")]
+    pub fn release_to_server(&mut self, bt: &BootServices) -> Result<()> {
+
+        let mode_data = self.mode_data()?.log();
+
+        let server = match mode_data.server_id() {
+            Some(server) if mode_data.is_bound_or_renewing() => server,
+            _ => return Err(Status::NOT_STARTED.into()),
+        };
+
+        let [a, b, c, d] = server.octets();
+        let mut buffer = mode_data.client_message(
+            options::DHCPRELEASE,
+            &[options::SERVER_IDENTIFIER, 4, a, b, c, d],
+        );
+
+        // No server answers a release, the timeout only bounds the wait
+        let mut token = TransmitReceiveToken{
+            remote_address: server.into(),
+            remote_port: SERVER_PORT,
+            timeout_value: 1,
+            packet: buffer.as_mut_ptr() as *const Packet,
+            ..TransmitReceiveToken::default()
+        };
+        let sent = self.transmit_receive(&mut token);
+        if !token.response_list.is_null() {
+            bt.free_pool(token.response_list as *mut u8)?.log();
+        }
+        sent?.log();
+
+        self.stop()
+
+    }
+
 }
 
 /// Configuration data for the DHCP4 protocol implementation
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct ConfigData {
     /// Number of times to try sending a packet during the
     /// Dhcp4SendDiscover event and waiting for a response during
@@ -108,14 +385,123 @@ pub struct ConfigData {
     /// The callback function to intercept various events that occurred in
     /// the DHCP configuration process. Set to NULL to ignore all those
     /// events. Type EFI_DHCP4_CALLBACK is defined below.
-    pub dhcp4_callback: Option<extern "efiapi" fn(
-        this: *mut DHCP4,
-        context: *mut c_void,
-        current_state: State,
-        dhcp4_event: Event,
-        packet: *const Packet,
-        new_packet: *mut *mut Packet,
-    ) -> Status>,
+    pub dhcp4_callback: Option<Callback>,
+    /// Pointer to the context that will be passed to Dhcp4Callback when it
+    /// is called.
+    pub callback_context: *mut c_void,
+    /// Number of DHCP options in the OptionList.
+    pub option_count: u32,
+    /// List of DHCP options to be included in every packet that is sent
+    /// during the Dhcp4InitReboot state.
+    pub option_list: *const *const PacketOption,
+}
+
+/// Callback intercepting the events of the DHCP configuration process.
+///
+/// The state and event are passed as raw values, as the firmware may report
+/// values this crate does not know, convert them with `State::try_from` and
+/// `Event::try_from`.
+pub type Callback = extern "efiapi" fn(
+    this: *mut DHCP4,
+    context: *mut c_void,
+    current_state: u32,
+    dhcp4_event: u32,
+    packet: *const Packet,
+    new_packet: *mut *mut Packet,
+) -> Status;
+
+/// UDP port DHCP servers listen on.
+pub const SERVER_PORT: u16 = 67;
+
+/// Describes a packet to transmit with `DHCP4::transmit_receive`, and
+/// receives the responses to it.
+#[repr(C)]
+pub struct TransmitReceiveToken {
+    /// Status of the transmission, set by the driver.
+    pub status: Status,
+    /// Event signaled once the responses are collected, or null to block.
+    pub completion_event: *mut c_void,
+    /// Address the packet is sent to.
+    pub remote_address: IPv4Address,
+    /// Port the packet is sent to, zero for the DHCP server port.
+    pub remote_port: u16,
+    /// Relay agent the packet is sent through, or zero.
+    pub gateway_address: IPv4Address,
+    /// Number of entries in `listen_points`.
+    pub listen_point_count: u32,
+    /// Addresses and ports to listen on for responses, the default client
+    /// port on the configured address when empty.
+    pub listen_points: *const ListenPoint,
+    /// Time in seconds to collect responses for.
+    pub timeout_value: u32,
+    /// Packet to transmit.
+    pub packet: *const Packet,
+    /// Number of responses received, set by the driver.
+    pub response_count: u32,
+    /// Responses received, stored one after the other in a buffer allocated
+    /// by the driver from pool memory.
+    pub response_list: *mut Packet,
+}
+
+impl Default for TransmitReceiveToken {
+    fn default() -> TransmitReceiveToken {
+        TransmitReceiveToken{
+            status: Status::SUCCESS,
+            completion_event: ptr::null_mut(),
+            remote_address: [0;4],
+            remote_port: 0,
+            gateway_address: [0;4],
+            listen_point_count: 0,
+            listen_points: ptr::null(),
+            timeout_value: 0,
+            packet: ptr::null(),
+            response_count: 0,
+            response_list: ptr::null_mut(),
+        }
+    }
+}
+
+#[cfg(feature = "exts")]
+impl TransmitReceiveToken {
+
+    // Find the first response carrying the given message type. The responses
+    // are stored one after the other, each taking `size` bytes.
+    fn response(&self, message_type: u8) -> Option<&Packet> {
+
+        let mut next = self.response_list as *const u8;
+        for _ in 0..self.response_count {
+            let response = unsafe { &*(next as *const Packet) };
+            if response.option(options::MESSAGE_TYPE) == Some(&[message_type]) {
+                return Some(response);
+            }
+            next = unsafe { next.add(response.size as usize) };
+        }
+        None
+
+    }
+
+}
+
+/// An address and port to listen on for responses to a transmitted packet.
+#[repr(C)]
+pub struct ListenPoint {
+    /// Local address to listen on.
+    pub listen_address: IPv4Address,
+    /// Subnet mask of the local address.
+    pub subnet_mask: IPv4Address,
+    /// Local port to listen on.
+    pub listen_port: u16,
+}
+
+/// A DHCP option as laid out in a packet, followed by `length` bytes of data.
+#[repr(C)]
+pub struct PacketOption {
+    /// DHCP option code.
+    pub op_code: u8,
+    /// Length of the option data in bytes.
+    pub length: u8,
+    /// Start of the option data.
+    pub data: [u8;1],
 }
 
 impl Default for ConfigData {
@@ -127,8 +513,237 @@ impl Default for ConfigData {
             request_timeout: ptr::null(),
             client_address: [0,0,0,0],
             dhcp4_callback: None,
+            callback_context: ptr::null_mut(),
+            option_count: 0,
+            option_list: ptr::null(),
+        }
+    }
+}
+
+/// Builds a `ConfigData` from Rust slices and types.
+///
+/// The built configuration borrows the timeout slices, so it cannot outlive
+/// them. The try counts always match the lengths of the timeout slices.
+pub struct ConfigDataBuilder<'a> {
+    discovery_timeouts: &'a [u32],
+    request_timeouts: &'a [u32],
+    client_address: Ipv4Addr,
+    callback: Option<Callback>,
+    callback_context: *mut c_void,
+}
+
+impl<'a> ConfigDataBuilder<'a> {
+
+    /// Create a builder for a configuration using the firmware defaults.
+    pub fn new() -> ConfigDataBuilder<'a> {
+        ConfigDataBuilder{
+            discovery_timeouts: &[],
+            request_timeouts: &[],
+            client_address: Ipv4Addr::default(),
+            callback: None,
+            callback_context: ptr::null_mut(),
+        }
+    }
+
+    /// Timeouts in seconds of each DHCPDISCOVER attempt.
+    pub fn discovery_timeouts(mut self, timeouts: &'a [u32]) -> Self {
+        self.discovery_timeouts = timeouts;
+        self
+    }
+
+    /// Timeouts in seconds of each DHCPREQUEST attempt.
+    pub fn request_timeouts(mut self, timeouts: &'a [u32]) -> Self {
+        self.request_timeouts = timeouts;
+        self
+    }
+
+    /// Previously allocated address to request, entering the Dhcp4InitReboot
+    /// state rather than Dhcp4Init. This is also the address a DHCPINFORM is
+    /// sent for, see `DHCP4::inform`.
+    pub fn client_address(mut self, address: Ipv4Addr) -> Self {
+        self.client_address = address;
+        self
+    }
+
+    /// Callback intercepting the events of the configuration process, along
+    /// with the context passed to it.
+    pub fn callback(mut self, callback: Callback, context: *mut c_void) -> Self {
+        self.callback = Some(callback);
+        self.callback_context = context;
+        self
+    }
+
+    /// Build the configuration.
+    pub fn build(&self) -> BuiltConfigData<'a> {
+
+        fn timeouts(t: &[u32]) -> (u32, *const u32) {
+            if t.is_empty() { (0, ptr::null()) } else { (t.len() as u32, t.as_ptr()) }
+        }
+
+        let (discovery_try_count, discovery_try_timeout) = timeouts(self.discovery_timeouts);
+        let (request_try_count, request_timeout) = timeouts(self.request_timeouts);
+
+        let config = ConfigData{
+            discovery_try_count,
+            discovery_try_timeout,
+            request_try_count,
+            request_timeout,
+            client_address: self.client_address.into(),
+            dhcp4_callback: self.callback,
+            callback_context: self.callback_context,
+            ..ConfigData::default()
+        };
+        BuiltConfigData{ config, _timeouts: PhantomData }
+
+    }
+
+}
+
+impl Default for ConfigDataBuilder<'_> {
+    fn default() -> Self { Self::new() }
+}
+
+/// A `ConfigData` built by `ConfigDataBuilder`, borrowing the timeout slices
+/// it points to.
+pub struct BuiltConfigData<'a> {
+    config: ConfigData,
+    _timeouts: PhantomData<&'a [u32]>,
+}
+
+impl core::ops::Deref for BuiltConfigData<'_> {
+
+    type Target = ConfigData;
+
+    fn deref(&self) -> &ConfigData { &self.config }
+
+}
+
+/// An event of a DHCP negotiation recorded by `DHCP4::capture_negotiation`.
+#[cfg(feature = "exts")]
+#[derive(Debug)]
+pub struct NegotiationEntry {
+    /// State of the driver when the event occurred.
+    pub state: State,
+    /// The event.
+    pub event: Event,
+    /// Copy of the packet associated with the event, if any, see
+    /// `Packet::to_vec`.
+    pub packet: Option<Vec<u8>>,
+}
+
+/// The ordered events of a DHCP negotiation, along with its outcome.
+#[cfg(feature = "exts")]
+#[derive(Debug)]
+pub struct NegotiationLog {
+    /// Events in the order they occurred.
+    pub entries: Vec<NegotiationEntry>,
+    /// Mode data once the negotiation completed.
+    pub mode_data: ModeData,
+}
+
+#[cfg(feature = "exts")]
+impl NegotiationLog {
+
+    /// Returns the events of the negotiation in order.
+    pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        self.entries.iter().map(|entry| entry.event)
+    }
+
+}
+
+/// Action requested by a closure registered through `CallbackConfig`.
+pub enum CallbackAction {
+    /// Continue the DHCP process with the current packet.
+    Continue,
+    /// Abort the DHCP process.
+    Abort,
+    /// Continue the DHCP process, replacing the current packet. The new
+    /// packet must be allocated from pool memory, the driver frees it.
+    ///
+    /// Only events about a packet to send or an offer to select accept a new
+    /// packet. For other events the packet is not taken, and the DHCP process
+    /// is aborted with `INVALID_PARAMETER`.
+    Replace(*mut Packet),
+}
+
+#[cfg(feature = "exts")]
+type Handler<'a> = dyn FnMut(State, Event, Option<&Packet>) -> CallbackAction + 'a;
+
+/// A `ConfigData` whose callback is a Rust closure.
+///
+/// The closure is boxed and passed to the driver through the callback
+/// context, with a trampoline translating between the two calling
+/// conventions. The closure receives no packet for events that have none.
+///
+/// The driver keeps a pointer to the closure, so this configuration must
+/// outlive its use by the driver, until the protocol is stopped or
+/// reconfigured.
+#[cfg(feature = "exts")]
+pub struct CallbackConfig<'a> {
+    config: ConfigData,
+    handler: Option<Box<Box<Handler<'a>>>>,
+}
+
+#[cfg(feature = "exts")]
+impl<'a> CallbackConfig<'a> {
+
+    /// Wrap a configuration, any callback it carries is replaced when a
+    /// closure is set.
+    pub fn new(config: ConfigData) -> CallbackConfig<'a> {
+        CallbackConfig{
+            config,
+            handler: None,
+        }
+    }
+
+    /// Set the closure intercepting the events of the configuration process.
+    pub fn set_callback(
+        &mut self,
+        f: impl FnMut(State, Event, Option<&Packet>) -> CallbackAction + 'a,
+    ) {
+
+        let mut handler: Box<Box<Handler<'a>>> = Box::new(Box::new(f));
+        self.config.dhcp4_callback = Some(Self::trampoline);
+        self.config.callback_context = &mut *handler as *mut Box<Handler<'a>> as *mut c_void;
+        self.handler = Some(handler);
+
+    }
+
+    /// Returns the configuration to pass to `DHCP4::configure`.
+    pub fn config(&self) -> &ConfigData {
+        &self.config
+    }
+
+    extern "efiapi" fn trampoline(
+        _this: *mut DHCP4,
+        context: *mut c_void,
+        current_state: u32,
+        dhcp4_event: u32,
+        packet: *const Packet,
+        new_packet: *mut *mut Packet,
+    ) -> Status {
+
+        // Let the driver carry on with events the closure cannot be told about
+        let (current_state, dhcp4_event) =
+            match (State::try_from(current_state), Event::try_from(dhcp4_event)) {
+                (Ok(state), Ok(event)) => (state, event),
+                _ => return Status::SUCCESS,
+            };
+
+        let handler = unsafe { &mut *(context as *mut Box<Handler>) };
+
+        match handler(current_state, dhcp4_event, unsafe { packet.as_ref() }) {
+            CallbackAction::Continue => Status::SUCCESS,
+            CallbackAction::Abort => Status::ABORTED,
+            CallbackAction::Replace(_) if new_packet.is_null() => Status::INVALID_PARAMETER,
+            CallbackAction::Replace(p) => {
+                unsafe { *new_packet = p; }
+                Status::SUCCESS
+            }
         }
+
     }
+
 }
 
 impl core::fmt::Debug for ConfigData {
@@ -139,37 +754,274 @@ impl core::fmt::Debug for ConfigData {
             .field("request_try_count", &self.request_try_count)
             .field("request_timeout", &self.request_timeout)
             .field("client_address", &self.client_address)
+            .field("callback_context", &self.callback_context)
+            .field("option_count", &self.option_count)
             .finish()
     }
 }
 
-/// An IPv4 address as a sequence of bytes.
+/// An IPv4 address as a sequence of bytes, the layout used by the firmware.
+/// Methods take and return the `Ipv4Addr` newtype instead.
 pub type IPv4Address = [u8;4];
 
+#[deprecated(note = "convert to `Ipv4Addr` and use its `Display` implementation")]
 pub fn show_ip(ip: &IPv4Address) -> String {
-    format!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3])
+    format!("{}", Ipv4Addr::from(*ip))
 }
 
-/// A MAC address as a sequence of bytes
-pub type MacAddress = [u8;32];
+/// An IPv4 address, displayed and parsed in dotted-quad notation.
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Ipv4Addr(pub IPv4Address);
+
+/// Error returned when parsing an `Ipv4Addr` from a malformed string.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AddrParseError;
+
+impl Ipv4Addr {
+
+    /// Create an address from its four octets.
+    pub const fn new(a: u8, b: u8, c: u8, d: u8) -> Ipv4Addr {
+        Ipv4Addr([a, b, c, d])
+    }
+
+    /// Returns the four octets of the address.
+    pub fn octets(&self) -> IPv4Address {
+        self.0
+    }
+
+    /// Returns true for the unspecified address 0.0.0.0.
+    pub fn is_unspecified(&self) -> bool {
+        self.0 == [0, 0, 0, 0]
+    }
+
+    /// Returns true for multicast addresses, 224.0.0.0/4.
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0xf0 == 224
+    }
+
+    /// Returns true for the limited broadcast address 255.255.255.255.
+    pub fn is_broadcast(&self) -> bool {
+        self.0 == [255, 255, 255, 255]
+    }
+
+    /// Returns the next address, wrapping from 255.255.255.255 to 0.0.0.0.
+    pub fn succ(self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self).wrapping_add(1))
+    }
+
+    /// Returns the Ethernet MAC address a multicast group is delivered to,
+    /// or `None` if this is not a multicast address.
+    ///
+    /// The low 23 bits of the group are mapped into the 01:00:5e prefix, as
+    /// described in RFC 1112.
+    pub fn multicast_mac(&self) -> Option<MacAddress> {
+
+        if !self.is_multicast() {
+            return None;
+        }
+
+        let [_, b, c, d] = self.0;
+        Some(MacAddress::from_bytes(&[0x01, 0x00, 0x5e, b & 0x7f, c, d]))
+
+    }
+
+}
+
+impl From<IPv4Address> for Ipv4Addr {
+    fn from(octets: IPv4Address) -> Ipv4Addr { Ipv4Addr(octets) }
+}
+
+impl From<Ipv4Addr> for IPv4Address {
+    fn from(addr: Ipv4Addr) -> IPv4Address { addr.0 }
+}
+
+impl From<u32> for Ipv4Addr {
+    fn from(addr: u32) -> Ipv4Addr { Ipv4Addr(addr.to_be_bytes()) }
+}
+
+impl From<Ipv4Addr> for u32 {
+    fn from(addr: Ipv4Addr) -> u32 { u32::from_be_bytes(addr.0) }
+}
+
+/// An inclusive range of IPv4 addresses, iterated in ascending order.
+///
+/// Iteration stops after `end`, and never wraps around past
+/// 255.255.255.255.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Range {
+    pub start: Ipv4Addr,
+    pub end: Ipv4Addr,
+}
+
+impl Ipv4Range {
+
+    /// Create a range from `start` to `end`, both included.
+    pub fn new(start: Ipv4Addr, end: Ipv4Addr) -> Ipv4Range {
+        Ipv4Range{ start, end }
+    }
+
+    /// The host addresses of the subnet containing `addr` with the given
+    /// prefix length, skipping the network and broadcast addresses.
+    ///
+    /// /31 and /32 subnets have no network or broadcast address, so all of
+    /// their addresses are included.
+    pub fn hosts(addr: Ipv4Addr, prefix_len: u8) -> Ipv4Range {
+
+        let mask = match prefix_len.min(32) {
+            0 => 0,
+            n => !0u32 << (32 - n),
+        };
+        let network = u32::from(addr) & mask;
+        let broadcast = network | !mask;
+
+        if prefix_len >= 31 {
+            Ipv4Range::new(network.into(), broadcast.into())
+        } else {
+            Ipv4Range::new((network + 1).into(), (broadcast - 1).into())
+        }
+
+    }
+
+}
+
+impl Iterator for Ipv4Range {
+
+    type Item = Ipv4Addr;
+
+    fn next(&mut self) -> Option<Ipv4Addr> {
+
+        let start = u32::from(self.start);
+        let end = u32::from(self.end);
+        if start > end {
+            return None;
+        }
+
+        if start == end {
+            // Leave an empty range behind instead of stepping past the end,
+            // which would wrap at 255.255.255.255.
+            self.start = Ipv4Addr::from(1u32);
+            self.end = Ipv4Addr::from(0u32);
+        } else {
+            self.start = self.start.succ();
+        }
+
+        Some(Ipv4Addr::from(start))
+
+    }
+
+}
+
+impl core::str::FromStr for Ipv4Addr {
+
+    type Err = AddrParseError;
+
+    fn from_str(s: &str) -> core::result::Result<Ipv4Addr, AddrParseError> {
+
+        let mut octets = [0u8;4];
+        let mut parts = s.split('.');
+
+        for octet in octets.iter_mut() {
+            let part = parts.next().ok_or(AddrParseError)?;
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(AddrParseError);
+            }
+            *octet = part.parse().map_err(|_| AddrParseError)?;
+        }
+
+        match parts.next() {
+            Some(_) => Err(AddrParseError),
+            None => Ok(Ipv4Addr(octets)),
+        }
+
+    }
+
+}
+
+impl core::fmt::Display for Ipv4Addr {
+
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.0[0], self.0[1], self.0[2], self.0[3])
+    }
+
+}
+
+impl core::fmt::Debug for Ipv4Addr {
+
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+
+}
+
+/// A MAC address, stored in the 32 byte `EFI_MAC_ADDRESS` layout.
+///
+/// Only the first few bytes are significant, 6 for Ethernet. The `Debug`
+/// implementation prints these as colon separated hex.
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct MacAddress(pub [u8;32]);
+
+/// Number of significant bytes in an Ethernet MAC address.
+pub const ETHERNET_ADDR_LEN: usize = 6;
+
+impl MacAddress {
+
+    /// Create a MAC address from its significant bytes, zero padding the
+    /// remainder. Bytes beyond the 32 byte capacity are ignored.
+    pub fn from_bytes(bytes: &[u8]) -> MacAddress {
+        let mut address = [0;32];
+        let len = bytes.len().min(address.len());
+        address[..len].copy_from_slice(&bytes[..len]);
+        MacAddress(address)
+    }
+
+    /// Returns all 32 bytes of the address.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+}
+
+impl From<[u8;32]> for MacAddress {
+    fn from(address: [u8;32]) -> MacAddress { MacAddress(address) }
+}
+
+impl core::ops::Deref for MacAddress {
+
+    type Target = [u8;32];
+
+    fn deref(&self) -> &[u8;32] { &self.0 }
+
+}
+
+impl core::ops::DerefMut for MacAddress {
+
+    fn deref_mut(&mut self) -> &mut [u8;32] { &mut self.0 }
 
-pub fn show_mac(m: &MacAddress) -> String {
-    format!("{:x}:{:x}:{:x}:{:x}:{:x}:{:x}", m[0], m[1], m[2], m[3], m[4], m[5])
 }
 
-/* XXX cannot do for type alias apparently and type MacAddress([u8;32]) does not
- * fly either
 impl core::fmt::Debug for MacAddress {
 
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.write_fmt(format_args!("{:02X?}", self))
+        for (i, b) in self.0[..ETHERNET_ADDR_LEN].iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
     }
 
 }
-*/
+
+pub fn show_mac(m: &MacAddress) -> String {
+    format!("{:?}", m)
+}
 
 /// The states the DHCP4 EFI protocol may be in
-#[derive(Debug, PartialEq)]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
 pub enum State {
     /// The EFI DHCPv4 Protocol driver is stopped.
     Dhcp4Stopped        = 0x0,
@@ -200,6 +1052,8 @@ impl Default for State {
 }
 
 /// Events emitted by the DHCP4 EFI protocol
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
 pub enum Event {
   /// The packet to start the configuration sequence is about to be sent.
   Dhcp4SendDiscover   = 0x01,
@@ -240,10 +1094,35 @@ pub struct PacketDHCP4 {
     pub header: Header,
     /// DHCP magik cookie in network byte order.
     pub magik: u32,
-    /// Start of the DHCP packed option data.
+    /// Start of the DHCP packed option data. The options are stored inline
+    /// from this field onwards, use `Packet::options` to read them.
     pub option: *mut u8,
 }
 
+/// Lease time denoting an infinite lease.
+pub const INFINITE_LEASE: u32 = 0xffff_ffff;
+
+/// DHCP magic cookie, in host byte order.
+pub const MAGIC_COOKIE: u32 = 0x6382_5363;
+
+/// Maximum hop count a relay agent forwards a message with, per RFC 1542.
+pub const MAX_HOPS: u8 = 16;
+
+impl PacketDHCP4 {
+
+    /// Set the magic cookie, written in network byte order so the packet
+    /// begins its options with the bytes `63 82 53 63`.
+    pub fn set_magic(&mut self) {
+        self.magik = MAGIC_COOKIE.to_be();
+    }
+
+    /// Returns true if the packet carries the DHCP magic cookie.
+    pub fn magic_valid(&self) -> bool {
+        u32::from_be(self.magik) == MAGIC_COOKIE
+    }
+
+}
+
 impl Default for PacketDHCP4 {
 
     fn default() -> PacketDHCP4 {
@@ -275,6 +1154,132 @@ pub struct Packet {
 
 }
 
+impl Packet {
+
+    /// Returns the raw options area of the packet.
+    ///
+    /// The options area starts at the `option` field and is bounded by both
+    /// the `length` of the packet and the `size` of the buffer holding it, so
+    /// a packet claiming more data than its buffer holds is truncated.
+    pub fn options_bytes(&self) -> &[u8] {
+
+        let hdr = size_of::<Header>() + size_of::<u32>();
+        let avail = (self.size as usize)
+            .saturating_sub(2 * size_of::<u32>())
+            .saturating_sub(hdr);
+        let len = (self.length as usize).saturating_sub(hdr).min(avail);
+
+        unsafe {
+            slice::from_raw_parts(&self.dhcp4.option as *const _ as *const u8, len)
+        }
+
+    }
+
+    /// Returns the writable options area of the packet, spanning the rest of
+    /// the buffer after the magic cookie, for editing options in place.
+    ///
+    /// # Safety
+    ///
+    /// The packet must be stored at the start of a writable buffer of at
+    /// least `size` bytes, as is the case for packets handed out by the
+    /// driver. A `Packet` value on its own carries no options area.
+    pub unsafe fn options_mut(&mut self) -> &mut [u8] {
+
+        let hdr = size_of::<Header>() + size_of::<u32>();
+        let len = (self.size as usize)
+            .saturating_sub(2 * size_of::<u32>())
+            .saturating_sub(hdr);
+
+        slice::from_raw_parts_mut(&mut self.dhcp4.option as *mut _ as *mut u8, len)
+
+    }
+
+    /// Copy the packet, from its `size` field to the end of its options, into
+    /// an owned buffer.
+    #[cfg(feature = "exts")]
+    pub fn to_vec(&self) -> Vec<u8> {
+
+        let hdr = 2 * size_of::<u32>() + size_of::<Header>() + size_of::<u32>();
+        let len = hdr + self.options_bytes().len();
+
+        unsafe {
+            slice::from_raw_parts(self as *const Packet as *const u8, len)
+        }.to_vec()
+
+    }
+
+    /// Iterate over the options of the packet.
+    ///
+    /// Pad options are skipped and iteration stops at the end option. Options
+    /// are never read past the `length` of the packet, a truncated trailing
+    /// option ends the iteration.
+    pub fn options(&self) -> OptionIterator<'_> {
+        self.options_bounded(usize::MAX)
+    }
+
+    /// Returns the data of the first option with the given code.
+    pub fn option(&self, code: u8) -> Option<&[u8]> {
+        self.options().find(|&(c, _)| c == code).map(|(_, data)| data)
+    }
+
+    /// Returns the data of all options with the given code joined together,
+    /// or `None` if the packet has no such option.
+    ///
+    /// RFC 3396 encodes options longer than 255 bytes as several instances of
+    /// the same code, whose data is concatenated in order.
+    #[cfg(feature = "exts")]
+    pub fn concatenated_option(&self, code: u8) -> Option<Vec<u8>> {
+
+        let mut data: Option<Vec<u8>> = None;
+        for (_, part) in self.options().filter(|&(c, _)| c == code) {
+            data.get_or_insert_with(Vec::new).extend_from_slice(part);
+        }
+        data
+
+    }
+
+    /// Returns every option of the packet keyed by code, with the data of
+    /// repeated codes concatenated as by `concatenated_option`.
+    #[cfg(feature = "exts")]
+    pub fn options_map(&self) -> BTreeMap<u8, Vec<u8>> {
+
+        let mut map = BTreeMap::new();
+        for (code, part) in self.options() {
+            map.entry(code).or_insert_with(Vec::new).extend_from_slice(part);
+        }
+        map
+
+    }
+
+    /// Check the options of fixed length, see `options::expected_len`,
+    /// returning the codes of the options whose length is wrong.
+    ///
+    /// Repeated options are checked once concatenated, as by
+    /// `concatenated_option`.
+    #[cfg(feature = "exts")]
+    pub fn validate_option_lengths(&self) -> core::result::Result<(), Vec<u8>> {
+
+        let bad: Vec<u8> = self.options_map()
+            .iter()
+            .filter(|&(&code, data)| {
+                matches!(options::expected_len(code), Some(len) if len != data.len())
+            })
+            .map(|(&code, _)| code)
+            .collect();
+
+        if bad.is_empty() { Ok(()) } else { Err(bad) }
+
+    }
+
+    /// Iterate over at most `max` options of the packet.
+    ///
+    /// This guards against replies carrying an excessive number of options.
+    pub fn options_bounded(&self, max: usize) -> OptionIterator<'_> {
+        OptionIterator::new(self.options_bytes(), max)
+    }
+
+}
+
 /// EFI_DHCP4_PACKET defines the format of DHCPv4 packets. See RFC 2131 for more information.
 #[repr(C)]
 pub struct Header {
@@ -307,10 +1312,94 @@ pub struct Header {
   /// Client hardware address.
   pub client_hw_addr: [u8;16],
   /// Optional server host name, null terminated string.
-  pub server_name: [char;64],
+  pub server_name: [u8;64],
   /// Boot file name, null terminated string; "generic" name or null in 
   /// DHCPDISCOVER, fully qualified directory-path name in DHCPOFFER.
-  pub bootfile_name: [char;128],
+  pub bootfile_name: [u8;128],
+}
+
+impl Header {
+
+    /// Returns the server host name, up to the first NUL byte.
+    pub fn server_name_str(&self) -> &str {
+        nul_terminated_str(&self.server_name)
+    }
+
+    /// Returns the boot file name, up to the first NUL byte.
+    pub fn bootfile_name_str(&self) -> &str {
+        nul_terminated_str(&self.bootfile_name)
+    }
+
+    /// Set the client address, `ciaddr`, of a client renewing, rebinding
+    /// or rebooting with a known address.
+    ///
+    /// Fails with `INVALID_PARAMETER` for multicast and broadcast addresses,
+    /// as do the other address setters.
+    pub fn set_client_addr(&mut self, addr: Ipv4Addr) -> Result<()> {
+        Self::set_addr(&mut self.client_addr, addr)
+    }
+
+    /// Set the address assigned to the client, `yiaddr`.
+    pub fn set_your_addr(&mut self, addr: Ipv4Addr) -> Result<()> {
+        Self::set_addr(&mut self.your_addr, addr)
+    }
+
+    /// Set the address of the next server in bootstrap, `siaddr`.
+    pub fn set_server_addr(&mut self, addr: Ipv4Addr) -> Result<()> {
+        Self::set_addr(&mut self.server_addr, addr)
+    }
+
+    /// Set the relay agent address, `giaddr`.
+    pub fn set_gateway_addr(&mut self, addr: Ipv4Addr) -> Result<()> {
+        Self::set_addr(&mut self.gateway_addr, addr)
+    }
+
+    fn set_addr(field: &mut IPv4Address, addr: Ipv4Addr) -> Result<()> {
+
+        if addr.is_multicast() || addr.is_broadcast() {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+        *field = addr.into();
+        Ok(().into())
+
+    }
+
+    /// Returns the number of relay agents the message passed through.
+    pub fn hops(&self) -> u8 {
+        self.hops
+    }
+
+    /// Set the relay hop count.
+    pub fn set_hops(&mut self, n: u8) {
+        self.hops = n;
+    }
+
+    /// Increment the relay hop count, as a relay agent forwarding the message
+    /// does.
+    ///
+    /// The count saturates at `MAX_HOPS`. Incrementing beyond it fails with
+    /// `ABORTED`, leaving the count unchanged, as the message must then be
+    /// discarded rather than forwarded.
+    pub fn increment_hops(&mut self) -> Result<()> {
+        if self.hops >= MAX_HOPS {
+            return Err(Status::ABORTED.into());
+        }
+        self.hops += 1;
+        Ok(().into())
+    }
+
+}
+
+// Decode a fixed size NUL padded header field, keeping the longest valid
+// UTF-8 prefix.
+fn nul_terminated_str(bytes: &[u8]) -> &str {
+
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    match str::from_utf8(&bytes[..end]) {
+        Ok(s) => s,
+        Err(e) => unsafe { str::from_utf8_unchecked(&bytes[..e.valid_up_to()]) },
+    }
+
 }
 
 impl Default for Header {
@@ -329,8 +1418,8 @@ impl Default for Header {
             server_addr: [0;4],
             gateway_addr: [0;4],
             client_hw_addr: [0;16], 
-            server_name: ['0';64],
-            bootfile_name: ['0';128],
+            server_name: [0;64],
+            bootfile_name: [0;128],
         }
     }
 
@@ -381,8 +1470,9 @@ impl Default for HardwareType {
 #[repr(C)]
 #[derive(Debug)]
 pub struct ModeData {
-  /// The EFI DHCPv4 Protocol driver operating state.
-  pub state: State,
+  /// The EFI DHCPv4 Protocol driver operating state, as written by the
+  /// firmware. Use `state()` to read it.
+  state: u32,
   /// The configuration data of the current EFI DHCPv4 Protocol driver instance.
   pub config_data: ConfigData,
   /// The client IP address that was acquired from the DHCP server. If it is zero,
@@ -405,14 +1495,237 @@ pub struct ModeData {
   pub reply_packet: *const Packet,
 }
 
+impl ModeData {
+
+    /// Build a message of the given type from the client, carrying `opts`
+    /// after the message type option, in a buffer holding a `Packet`.
+    #[cfg(feature = "exts")]
+    fn client_message(&self, message_type: u8, opts: &[u8]) -> Vec<u64> {
+
+        let hdr = size_of::<Header>() + size_of::<u32>();
+        let length = hdr + 3 + opts.len() + 1;
+        let size = (2 * size_of::<u32>() + length).max(size_of::<Packet>());
+
+        let mut buffer = vec![0u64; size / size_of::<u64>() + 1];
+        let packet = unsafe { &mut *(buffer.as_mut_ptr() as *mut Packet) };
+        packet.size = (buffer.len() * size_of::<u64>()) as u32;
+        packet.length = length as u32;
+
+        let header = &mut packet.dhcp4.header;
+        header.op_code = OpCode::BootRequest;
+        header.hw_type = HardwareType::Ethernet;
+        header.hw_addr_len = 6;
+        // the driver refuses the transaction ID of its own negotiation
+        header.xid = self.reply().map_or(0, |reply| reply.dhcp4.header.xid).wrapping_add(1);
+        header.client_addr = self.client_address;
+        header.client_hw_addr[..6].copy_from_slice(&self.client_mac_address[..6]);
+        packet.dhcp4.set_magic();
+
+        let area = unsafe { packet.options_mut() };
+        area[..3].copy_from_slice(&[options::MESSAGE_TYPE, 1, message_type]);
+        area[3..3 + opts.len()].copy_from_slice(opts);
+        area[3 + opts.len()] = options::END;
+
+        buffer
+
+    }
+
+    /// Returns the operating state of the driver, or an error if the
+    /// firmware reported a value this crate does not know.
+    pub fn state(&self) -> core::result::Result<State, TryFromPrimitiveError<State>> {
+        State::try_from(self.state)
+    }
+
+    /// Sets the operating state.
+    pub fn set_state(&mut self, state: State) {
+        self.state = state.into();
+    }
+
+    /// Returns the cached reply packet, if any.
+    pub fn reply(&self) -> Option<&Packet> {
+        unsafe { self.reply_packet.as_ref() }
+    }
+
+    /// Returns the duration of the lease, or `None` for an infinite lease.
+    pub fn lease_duration(&self) -> Option<Duration> {
+        match self.lease_time {
+            INFINITE_LEASE => None,
+            secs => Some(Duration::from_secs(secs as u64)),
+        }
+    }
+
+    /// Returns true once `elapsed` since the lease was granted exceeds T1,
+    /// half of the lease, at which point RFC 2131 has the client renew it
+    /// with the server that granted it. Infinite leases are never renewed.
+    pub fn should_renew(&self, elapsed: Duration) -> bool {
+        match self.lease_duration() {
+            Some(lease) => elapsed > lease / 2,
+            None => false,
+        }
+    }
+
+    /// Returns true once `elapsed` since the lease was granted exceeds T2,
+    /// 87.5% of the lease, at which point RFC 2131 has the client rebind it
+    /// with any server. Infinite leases are never rebound.
+    pub fn should_rebind(&self, elapsed: Duration) -> bool {
+        match self.lease_duration() {
+            Some(lease) => elapsed > lease * 7 / 8,
+            None => false,
+        }
+    }
+
+    /// Returns true while the client renews or rebinds its lease, during
+    /// which it keeps using its address.
+    pub fn is_renewing(&self) -> bool {
+        matches!(self.state(), Ok(State::Dhcp4Renewing) | Ok(State::Dhcp4Rebinding))
+    }
+
+    /// Returns true if the driver has been configured, i.e. it is in any
+    /// state but `Dhcp4Stopped`.
+    pub fn is_configured(&self) -> bool {
+        !matches!(self.state(), Ok(State::Dhcp4Stopped))
+    }
+
+    /// Returns true if the client has an address it can use, either bound
+    /// or while renewing or rebinding its lease.
+    pub fn is_bound_or_renewing(&self) -> bool {
+        matches!(self.state(), Ok(State::Dhcp4Bound)) || self.is_renewing()
+    }
+
+    /// Returns the subnet mask of the lease.
+    ///
+    /// The `subnet_mask` field is preferred, falling back to the subnet mask
+    /// option of the cached reply for firmware that leaves the field zero.
+    pub fn subnet_mask_any(&self) -> Option<Ipv4Addr> {
+
+        if self.subnet_mask != [0;4] {
+            return Some(self.subnet_mask.into());
+        }
+
+        match self.reply()?.option(options::SUBNET_MASK) {
+            Some(&[a, b, c, d]) => Some(Ipv4Addr::new(a, b, c, d)),
+            _ => None,
+        }
+
+    }
+
+    /// Returns the address offered or assigned to the client by the cached
+    /// reply, its `your_addr` header field.
+    ///
+    /// Unlike `client_address`, which is only set once the lease is bound,
+    /// this is available while selecting among offers. Returns `None` when no
+    /// reply is cached or the reply assigns no address.
+    pub fn offered_address(&self) -> Option<Ipv4Addr> {
+
+        let addr = Ipv4Addr::from(self.reply()?.dhcp4.header.your_addr);
+        if addr.is_unspecified() { None } else { Some(addr) }
+
+    }
+
+    /// Returns the identifier of the server that granted the lease.
+    ///
+    /// This is the server identifier option of the cached reply, falling back
+    /// to the `server_addr` header field of the reply when the option is
+    /// absent.
+    pub fn server_id(&self) -> Option<Ipv4Addr> {
+
+        let reply = self.reply()?;
+
+        let server = match reply.option(options::SERVER_IDENTIFIER) {
+            Some(&[a, b, c, d]) => Ipv4Addr::new(a, b, c, d),
+            _ => Ipv4Addr::from(reply.dhcp4.header.server_addr),
+        };
+
+        if server.is_unspecified() { None } else { Some(server) }
+
+    }
+
+    /// Returns the domain name offered by the server, used as the search
+    /// domain when resolving short hostnames.
+    ///
+    /// Returns `None` when the option is absent, empty or not valid UTF-8.
+    pub fn domain_name(&self) -> Option<&str> {
+        self.option_str(options::DOMAIN_NAME)
+    }
+
+    /// Returns the name of the TFTP server, often a hostname to resolve
+    /// rather than the `server_addr` of the header.
+    ///
+    /// Returns `None` when the option is absent, empty or not valid UTF-8.
+    pub fn tftp_server_name(&self) -> Option<&str> {
+        self.option_str(options::TFTP_SERVER_NAME)
+    }
+
+    /// Returns true if the cached reply comes from a PXE server, i.e. carries
+    /// a vendor class identifier starting with `PXEClient`.
+    ///
+    /// A plain DHCP reply, or no reply at all, returns false.
+    pub fn is_pxe_reply(&self) -> bool {
+
+        let class = self.reply().and_then(|reply| reply.option(options::VENDOR_CLASS_IDENTIFIER));
+        matches!(class, Some(class) if class.starts_with(b"PXEClient"))
+
+    }
+
+    /// Returns the NTP servers of the cached reply, in order of preference.
+    ///
+    /// Returns an empty list when the option is absent. Trailing bytes not
+    /// forming a whole address are ignored.
+    #[cfg(feature = "exts")]
+    pub fn ntp_servers(&self) -> Vec<Ipv4Addr> {
+
+        match self.reply().and_then(|reply| reply.option(options::NTP_SERVERS)) {
+            Some(data) => data
+                .chunks_exact(4)
+                .map(|a| Ipv4Addr::new(a[0], a[1], a[2], a[3]))
+                .collect(),
+            None => Vec::new(),
+        }
+
+    }
+
+    // Decode a string option of the cached reply.
+    fn option_str(&self, code: u8) -> Option<&str> {
+
+        let s = self.reply()?.option(code)?;
+
+        // Some servers include a trailing NUL in the option
+        let s = match s.split_last() {
+            Some((0, rest)) => rest,
+            _ => s,
+        };
+
+        str::from_utf8(s).ok().filter(|s| !s.is_empty())
+
+    }
+
+    /// Returns the boot file name offered by the server.
+    ///
+    /// The boot file name option is preferred, falling back to the `file`
+    /// field of the header used by BOOTP style replies.
+    pub fn boot_filename(&self) -> Option<&str> {
+
+        let reply = self.reply()?;
+
+        let name = reply.option(options::BOOTFILE_NAME)
+            .map(nul_terminated_str)
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| reply.dhcp4.header.bootfile_name_str());
+
+        if name.is_empty() { None } else { Some(name) }
+
+    }
+
+}
+
 impl Default for ModeData {
 
     fn default() -> ModeData {
         ModeData{
-            state: State::default(),
+            state: State::default().into(),
             config_data: ConfigData::default(),
             client_address: [0;4],
-            client_mac_address: [0;32],
+            client_mac_address: MacAddress::default(),
             server_address: [0;4],
             router_address: [0;4],
             subnet_mask: [0;4],
@@ -422,3 +1735,57 @@ impl Default for ModeData {
     }
 
 }
+
+/// Scheme of an HTTP boot URI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    /// Plain HTTP, on port 80 by default.
+    Http,
+    /// HTTP over TLS, on port 443 by default.
+    Https,
+}
+
+impl Scheme {
+
+    /// Returns the port used when the URI gives none.
+    pub fn default_port(self) -> u16 {
+        match self {
+            Scheme::Http => 80,
+            Scheme::Https => 443,
+        }
+    }
+
+}
+
+/// Split an HTTP boot URI, as offered in the boot file name of an HTTP boot
+/// reply, into its scheme, host, port and path.
+///
+/// The port defaults to that of the scheme, and the path to `/`. Returns
+/// `None` for schemes other than `http` and `https`, a missing host, an
+/// invalid port, or an IPv6 literal host.
+pub fn parse_boot_uri(uri: &str) -> Option<(Scheme, &str, u16, &str)> {
+
+    let sep = uri.find("://")?;
+    let scheme = match &uri[..sep] {
+        s if s.eq_ignore_ascii_case("http") => Scheme::Http,
+        s if s.eq_ignore_ascii_case("https") => Scheme::Https,
+        _ => return None,
+    };
+
+    let rest = &uri[sep + 3..];
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+
+    let (host, port) = match authority.find(':') {
+        Some(i) => (&authority[..i], authority[i + 1..].parse().ok()?),
+        None => (authority, scheme.default_port()),
+    };
+    if host.is_empty() || host.starts_with('[') {
+        return None;
+    }
+
+    Some((scheme, host, port, path))
+
+}
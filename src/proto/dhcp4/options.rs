@@ -0,0 +1,162 @@
+//! DHCP4 packet options
+//!
+//! DHCP options are carried as a stream of type-length-value encoded entries
+//! following the magic cookie of a packet, as described in RFC 2132.
+
+/// Padding option, a single byte with no length or data.
+pub const PAD: u8 = 0;
+
+/// End option, marks the end of valid information in the options field.
+pub const END: u8 = 255;
+
+/// Subnet mask of the client.
+pub const SUBNET_MASK: u8 = 1;
+
+/// List of routers on the client's subnet, in order of preference.
+pub const ROUTER: u8 = 3;
+
+/// List of DNS servers available to the client, in order of preference.
+pub const DOMAIN_NAME_SERVER: u8 = 6;
+
+/// Domain name the client should use when resolving hostnames via DNS.
+pub const DOMAIN_NAME: u8 = 15;
+
+/// List of NTP servers available to the client, in order of preference.
+pub const NTP_SERVERS: u8 = 42;
+
+/// Address requested by the client in a DHCPDISCOVER or DHCPREQUEST.
+pub const REQUESTED_IP_ADDRESS: u8 = 50;
+
+/// Lease time of the address in seconds.
+pub const IP_ADDRESS_LEASE_TIME: u8 = 51;
+
+/// Indicates the `sname` and `file` header fields carry options.
+pub const OPTION_OVERLOAD: u8 = 52;
+
+/// Type of the DHCP message, e.g. DHCPOFFER or DHCPACK.
+pub const MESSAGE_TYPE: u8 = 53;
+
+/// Message type of a DHCPACK, sent by a server granting a request.
+pub const DHCPACK: u8 = 5;
+
+/// Message type of a DHCPRELEASE, sent by a client giving up its lease.
+pub const DHCPRELEASE: u8 = 7;
+
+/// Message type of a DHCPINFORM, sent by a client with an address asking
+/// for its other configuration parameters.
+pub const DHCPINFORM: u8 = 8;
+
+/// Address of the server that sent the packet, used by clients to address
+/// their messages to the server.
+pub const SERVER_IDENTIFIER: u8 = 54;
+
+/// Name of the TFTP server, used when the `sname` header field is used for
+/// options.
+pub const TFTP_SERVER_NAME: u8 = 66;
+
+/// Maximum size of a DHCP message the sender accepts.
+pub const MAXIMUM_MESSAGE_SIZE: u8 = 57;
+
+/// Time in seconds from address assignment until the client enters the
+/// renewing state.
+pub const RENEWAL_TIME: u8 = 58;
+
+/// Time in seconds from address assignment until the client enters the
+/// rebinding state.
+pub const REBINDING_TIME: u8 = 59;
+
+/// Vendor class of the sender. PXE servers reply with a class starting with
+/// `PXEClient`.
+pub const VENDOR_CLASS_IDENTIFIER: u8 = 60;
+
+/// Boot file name, used when the `file` header field is used for options.
+pub const BOOTFILE_NAME: u8 = 67;
+
+/// Returns the length of the data of options with a fixed length, or `None`
+/// for options of variable length and unknown options.
+pub fn expected_len(code: u8) -> Option<usize> {
+
+    match code {
+        SUBNET_MASK
+        | REQUESTED_IP_ADDRESS
+        | IP_ADDRESS_LEASE_TIME
+        | SERVER_IDENTIFIER
+        | RENEWAL_TIME
+        | REBINDING_TIME => Some(4),
+        MAXIMUM_MESSAGE_SIZE => Some(2),
+        OPTION_OVERLOAD | MESSAGE_TYPE => Some(1),
+        _ => None,
+    }
+
+}
+
+/// Iterator over the options of a DHCP4 packet.
+///
+/// Yields `(code, data)` pairs. Pad options are skipped and iteration stops at
+/// the end option, at the end of the options area, or when an option would
+/// extend past the end of the options area.
+pub struct OptionIterator<'a> {
+    data: &'a [u8],
+    offset: usize,
+    remaining: usize,
+}
+
+impl<'a> OptionIterator<'a> {
+
+    /// Create an iterator over a raw options area that yields at most `max`
+    /// options.
+    pub fn new(data: &'a [u8], max: usize) -> OptionIterator<'a> {
+        OptionIterator{
+            data,
+            offset: 0,
+            remaining: max,
+        }
+    }
+
+    fn finish(&mut self) -> Option<(u8, &'a [u8])> {
+        self.offset = self.data.len();
+        self.remaining = 0;
+        None
+    }
+
+}
+
+impl<'a> Iterator for OptionIterator<'a> {
+
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        if self.remaining == 0 {
+            return None;
+        }
+
+        // skip a whole run of pad options at once, heavily padded options
+        // areas are common
+        let rest = self.data.get(self.offset..).unwrap_or(&[]);
+        self.offset += rest.iter().position(|&b| b != PAD).unwrap_or(rest.len());
+
+        let code = match self.data.get(self.offset) {
+            Some(&END) | None => return self.finish(),
+            Some(&code) => code,
+        };
+
+        let len = match self.data.get(self.offset + 1) {
+            Some(&len) => len as usize,
+            None => return self.finish(),
+        };
+
+        let start = self.offset + 2;
+        let data = match self.data.get(start..start + len) {
+            Some(data) => data,
+            None => return self.finish(),
+        };
+
+        self.offset = start + len;
+        self.remaining -= 1;
+
+        Some((code, data))
+
+    }
+
+}
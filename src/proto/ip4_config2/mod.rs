@@ -2,17 +2,31 @@
 
 use crate::proto::{
     Protocol,
+    loaded_image::LoadOptionsError,
     dhcp4::{
         MacAddress,
-        IPv4Address
+        IPv4Address,
+        Ipv4Addr,
     }
 };
 use crate::{unsafe_guid, Result, Status, Event};
 use core::{
+    convert::TryFrom,
     ptr,
+    slice,
+    str,
     ffi::c_void,
+    mem::{size_of, size_of_val},
 };
+#[cfg(feature = "exts")]
+use core::ops::Deref;
+#[cfg(feature = "exts")]
+use alloc_api::{vec, vec::Vec};
 use log::info;
+use num_enum::{
+    TryFromPrimitive,
+    IntoPrimitive,
+};
 
 /// The EFI_IP4_CONFIG2_PROTOCOL provides the mechanism to set and get various types of
 /// configurations for the EFI IPv4 network stack.
@@ -31,7 +45,7 @@ pub struct IP4Config2 {
     get_data: extern "efiapi" fn(
         this: &mut IP4Config2,
         data_type: DataType,
-        data_size: usize,
+        data_size: &mut usize,
         data: *mut c_void,
     ) -> Status,
 
@@ -40,9 +54,12 @@ pub struct IP4Config2 {
         data_type: DataType,
         event: Event,
     ) -> Status,
-        
 
-    unregister_data_notify: usize,
+    unregister_data_notify: extern "efiapi" fn(
+        this: &mut IP4Config2,
+        data_type: DataType,
+        event: Event,
+    ) -> Status,
 }
 
 impl IP4Config2 {
@@ -58,43 +75,272 @@ impl IP4Config2 {
 
     }
 
+    /// Get the configuration data of the specified type. On input `size` is
+    /// the size of `data` in bytes, on output it is the size of the
+    /// configuration data, which is also set when `BUFFER_TOO_SMALL` is
+    /// returned.
     pub fn get_data(
-        &mut self, data_type: DataType, size: usize, data: *mut c_void
+        &mut self, data_type: DataType, size: &mut usize, data: *mut c_void
     ) -> Result<()> {
 
         (self.get_data)(self, data_type, size, data).into()
 
     }
 
+    /// Get the interface information of the communication device.
+    ///
+    /// The route table is copied out of the firmware's buffer and owned by
+    /// the returned value, so its `route_table` pointer stays valid for as
+    /// long as the value lives.
+    #[cfg(feature = "exts")]
+    pub fn interface_info(&mut self) -> Result<OwnedInterfaceInfo> {
+
+        let mut size = 0;
+        let status = (self.get_data)(
+            self, DataType::InterfaceInfo, &mut size, ptr::null_mut());
+        if status != Status::BUFFER_TOO_SMALL && status.is_error() {
+            return Err(status.into());
+        }
+
+        // A u64 buffer keeps the pointer fields of the info suitably aligned.
+        let words = size.max(size_of::<InterfaceInfo>()) / 8 + 1;
+        let mut buffer: Vec<u64> = vec![0; words];
+        let mut size = words * 8;
+
+        let status = (self.get_data)(
+            self,
+            DataType::InterfaceInfo,
+            &mut size,
+            buffer.as_mut_ptr() as *mut c_void,
+        );
+        if status.is_error() {
+            return Err(status.into());
+        }
+
+        let mut info = unsafe {
+            ptr::read(buffer.as_ptr() as *const InterfaceInfo)
+        };
+        let mut routes = if info.route_table.is_null() {
+            Vec::new()
+        } else {
+            unsafe {
+                slice::from_raw_parts(
+                    info.route_table, info.route_table_size as usize)
+            }.to_vec()
+        };
+        info.route_table = routes.as_mut_ptr();
+        info.route_table_size = routes.len() as u32;
+
+        status.into_with_val(|| OwnedInterfaceInfo{ info, _routes: routes })
+
+    }
+
+    /// Set the configuration policy of the IPv4 network stack.
+    ///
+    /// Switching to `Policy::Dhcp` is allowed at any time, including before
+    /// a manual address has been set. `Policy::Maximum` is not a policy and
+    /// is rejected with `INVALID_PARAMETER`.
+    pub fn set_policy(&mut self, policy: Policy) -> Result<()> {
+
+        if policy == Policy::Maximum {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+
+        let raw: u32 = policy.into();
+        self.set_data(
+            DataType::Policy,
+            size_of::<u32>(),
+            &raw as *const u32 as *const c_void,
+        )
+
+    }
+
+    /// Get the configuration policy of the IPv4 network stack.
+    ///
+    /// Fails with `PROTOCOL_ERROR` if the firmware reports a value that is not
+    /// a known policy.
+    pub fn policy(&mut self) -> Result<Policy> {
+
+        let mut raw = 0u32;
+        let mut size = size_of::<u32>();
+        self.get_data(
+            DataType::Policy,
+            &mut size,
+            &mut raw as *mut u32 as *mut c_void,
+        )?.log();
+
+        match Policy::try_from(raw) {
+            Ok(Policy::Maximum) | Err(_) => Err(Status::PROTOCOL_ERROR.into()),
+            Ok(policy) => Ok(policy.into()),
+        }
+
+    }
+
+    /// Set the station address and subnet mask of the IPv4 network stack.
+    ///
+    /// Manual addresses are only configurable under `Policy::Static`, this
+    /// fails with `WRITE_PROTECTED` if the current policy is `Policy::Dhcp`.
+    pub fn set_manual_address(
+        &mut self, address: Ipv4Addr, subnet_mask: Ipv4Addr
+    ) -> Result<()> {
+
+        if self.policy()?.log() == Policy::Dhcp {
+            return Err(Status::WRITE_PROTECTED.into());
+        }
+
+        let manual = ManualAddress{
+            address: address.into(),
+            subnet_mask: subnet_mask.into(),
+        };
+        self.set_data(
+            DataType::ManualAddress,
+            size_of::<ManualAddress>(),
+            &manual as *const ManualAddress as *const c_void,
+        )
+
+    }
+
+    /// Set the DNS server list of the IPv4 network stack.
+    ///
+    /// The DNS servers are only configurable under `Policy::Static`, this
+    /// fails with `WRITE_PROTECTED` if the current policy is `Policy::Dhcp`.
+    pub fn set_dns_servers(&mut self, servers: &[Ipv4Addr]) -> Result<()> {
+
+        if self.policy()?.log() == Policy::Dhcp {
+            return Err(Status::WRITE_PROTECTED.into());
+        }
+
+        self.set_data(
+            DataType::DnsServer,
+            size_of_val(servers),
+            servers.as_ptr() as *const c_void,
+        )
+
+    }
+
+    /// Get the DNS server list of the IPv4 network stack. An empty list is
+    /// returned if no DNS servers are configured.
+    #[cfg(feature = "exts")]
+    pub fn dns_servers(&mut self) -> Result<Vec<Ipv4Addr>> {
+        self.addresses(DataType::DnsServer)
+    }
+
+    /// Set the gateway addresses of the IPv4 network stack.
+    ///
+    /// Gateways must be unicast addresses, the unspecified, broadcast and
+    /// multicast addresses are rejected with `INVALID_PARAMETER`. The
+    /// gateways are only configurable under `Policy::Static`, this fails with
+    /// `WRITE_PROTECTED` if the current policy is `Policy::Dhcp`.
+    pub fn set_gateways(&mut self, gateways: &[Ipv4Addr]) -> Result<()> {
+
+        let unicast = gateways.iter().all(|gw| {
+            !(gw.is_unspecified() || gw.is_broadcast() || gw.is_multicast())
+        });
+        if !unicast {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+
+        if self.policy()?.log() == Policy::Dhcp {
+            return Err(Status::WRITE_PROTECTED.into());
+        }
+
+        self.set_data(
+            DataType::Gateway,
+            size_of_val(gateways),
+            gateways.as_ptr() as *const c_void,
+        )
+
+    }
+
+    /// Get the gateway addresses of the IPv4 network stack. An empty list is
+    /// returned if no gateways are configured.
+    #[cfg(feature = "exts")]
+    pub fn gateways(&mut self) -> Result<Vec<Ipv4Addr>> {
+        self.addresses(DataType::Gateway)
+    }
+
+    /// Get configuration data consisting of an array of addresses.
+    #[cfg(feature = "exts")]
+    fn addresses(&mut self, data_type: DataType) -> Result<Vec<Ipv4Addr>> {
+
+        let mut size = 0;
+        match (self.get_data)(self, data_type, &mut size, ptr::null_mut()) {
+            Status::BUFFER_TOO_SMALL => {},
+            Status::NOT_FOUND => return Ok(Vec::new().into()),
+            status if status.is_error() => return Err(status.into()),
+            status => return status.into_with_val(Vec::new),
+        }
+
+        // Ipv4Addr is transparent over the wire format
+        let mut addresses = vec![Ipv4Addr::default(); size / size_of::<Ipv4Addr>()];
+        let mut size = addresses.len() * size_of::<Ipv4Addr>();
+        let status = (self.get_data)(
+            self,
+            data_type,
+            &mut size,
+            addresses.as_mut_ptr() as *mut c_void,
+        );
+        if status.is_error() {
+            return Err(status.into());
+        }
+
+        addresses.truncate(size / size_of::<Ipv4Addr>());
+        status.into_with_val(|| addresses)
+
+    }
+
     /// Register an event that is to be signaled whenever a configuration
     /// process on the specified configuration data is done.
+    ///
+    /// The event stays owned by the caller, only its handle is passed to the
+    /// firmware.
     pub fn register_data_notify(
-        &mut self, data_type: DataType, event: Event) -> Result<()> {
+        &mut self, data_type: DataType, event: &Event) -> Result<()> {
 
-        (self.register_data_notify)(self, data_type, event).into()
+        (self.register_data_notify)(self, data_type, *event).into()
+
+    }
+
+    /// Remove a previously registered event for the specified configuration
+    /// data.
+    ///
+    /// Fails with `NOT_FOUND` if the event was not registered for this data
+    /// type.
+    ///
+    /// ```ignore
+    /// let event = unsafe { bt.create_event(EventType::NOTIFY_SIGNAL, Tpl::CALLBACK, Some(notify)) }?.log();
+    /// ip4_config2.register_data_notify(DataType::InterfaceInfo, &event)?.log();
+    /// // ... wait for the configuration to change ...
+    /// ip4_config2.unregister_data_notify(DataType::InterfaceInfo, &event)?.log();
+    /// ```
+    pub fn unregister_data_notify(
+        &mut self, data_type: DataType, event: &Event) -> Result<()> {
+
+        (self.unregister_data_notify)(self, data_type, *event).into()
 
     }
 }
 
 /// A unique key that defines what sort of data to get or set in an IPv4 config.
-#[repr(C)]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
 pub enum DataType {
     /// The interface information of the communication device this EFI
     /// IPv4 Configuration II Protocol instance manages. This type of
     /// data is read only. The corresponding Data is of type
     /// EFI_IP4_CONFIG2_INTERFACE_INFO.
-    InterfaceInfo,
+    InterfaceInfo = 0,
     /// The general configuration policy for the EFI IPv4 network stack
     /// running on the communication device this EFI IPv4
     /// Configuration II Protocol instance manages. The policy will
     /// affect other configuration settings. The corresponding Data is of
     /// type EFI_IP4_CONFIG2_POLICY.
-    Policy,
+    Policy = 1,
     /// The station addresses set manually for the EFI IPv4 network
     /// stack. It is only configurable when the policy is
     /// Ip4Config2PolicyStatic. The corresponding Data is of
     /// type EFI_IP4_CONFIG2_MANUAL_ADDRESS.
-    ManualAddress,
+    ManualAddress = 2,
     /// The gateway addresses set manually for the EFI IPv4 network
     /// stack running on the communication device this EFI IPv4
     /// Configuration II Protocol manages. It is not configurable when
@@ -102,34 +348,45 @@ pub enum DataType {
     /// addresses must be unicast IPv4 addresses. The corresponding
     /// Data is a pointer to an array of EFI_IPv4_ADDRESS
     /// instances.
-    Gateway,
+    Gateway = 3,
     /// The DNS server list for the EFI IPv4 network stack running on
     /// the communication device this EFI IPv4 Configuration II
     /// Protocol manages. It is not configurable when the policy is
     /// Ip4Config2PolicyDhcp.The DNS server addresses must be
     /// unicast IPv4 addresses. The corresponding Data is a pointer to
     /// an array of EFI_IPv4_ADDRESS instances.
-    DnsServer,
+    DnsServer = 4,
     /// Identifies an uppper bound on data types
-    Maximum
+    Maximum = 5,
 }
 
 /// EFI_IP4_CONFIG2_POLICY
-#[repr(C)]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
 pub enum Policy {
   /// Under this policy, the Ip4Config2DataTypeManualAddress,
   /// Ip4Config2DataTypeGateway and Ip4Config2DataTypeDnsServer configuration
   /// data are required to be set manually. The EFI IPv4 Protocol will get all
   /// required configuration such as IPv4 address, subnet mask and
   /// gateway settings from the EFI IPv4 Configuration II protocol.
-  Static,
+  Static = 0,
   /// Under this policy, the Ip4Config2DataTypeManualAddress,
   /// Ip4Config2DataTypeGateway and Ip4Config2DataTypeDnsServer configuration data are
   /// not allowed to set via SetData(). All of these configurations are retrieved from DHCP
   /// server or other auto-configuration mechanism.
-  Dhcp,
+  Dhcp = 1,
   /// Identifies and upper bound on data types
-  Maximum
+  Maximum = 2,
+}
+
+/// EFI_IP4_CONFIG2_MANUAL_ADDRESS
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ManualAddress {
+    /// The IPv4 unicast address.
+    pub address: IPv4Address,
+    /// The subnet mask.
+    pub subnet_mask: IPv4Address,
 }
 
 #[repr(C)]
@@ -152,7 +409,7 @@ impl InterfaceInfo {
             name: [0;32],
             iftype: 0,
             hw_address_size: 0,
-            hw_address: [0;32],
+            hw_address: MacAddress::default(),
             station_address: [0;4],
             subnet_mask: [0;4],
             route_table_size: 0,
@@ -160,21 +417,68 @@ impl InterfaceInfo {
         }
     }
 
-    pub fn dump_route_table(&self) {
+    /// Decode the name of the interface into `buffer`, e.g. "eth0".
+    ///
+    /// The name ends at the first NUL, or fills all 32 code units when it
+    /// has no terminator.
+    pub fn name<'a>(
+        &self, buffer: &'a mut [u8]
+    ) -> core::result::Result<&'a str, LoadOptionsError> {
 
-        for i in 0..(self.route_table_size as usize) {
+        let len = self.name.iter().position(|&c| c == 0).unwrap_or(self.name.len());
+        let length = ucs2::decode(&self.name[..len], buffer)
+            .map_err(|_| LoadOptionsError::BufferTooSmall)?;
+        str::from_utf8(&buffer[..length]).map_err(|_| LoadOptionsError::NotValidUtf8)
 
-            unsafe { 
-                info!("{:?}", &*self.route_table.add(i));
+    }
+
+    /// Iterate over the entries of the route table.
+    pub fn routes(&self) -> impl Iterator<Item = &RouteTableEntry> {
+
+        let routes: &[RouteTableEntry] = if self.route_table.is_null() {
+            &[]
+        } else {
+            unsafe {
+                slice::from_raw_parts(
+                    self.route_table, self.route_table_size as usize)
             }
+        };
+        routes.iter()
+
+    }
 
+    pub fn dump_route_table(&self) {
+
+        for route in self.routes() {
+            info!("{:?}", route);
         }
 
     }
 }
 
-#[repr(C)]
+/// Interface information that owns its route table.
+///
+/// Dereferences to an `InterfaceInfo` whose `route_table` points into the
+/// owned entries.
+#[cfg(feature = "exts")]
 #[derive(Debug)]
+pub struct OwnedInterfaceInfo {
+    info: InterfaceInfo,
+    // Backing storage for `info.route_table`.
+    _routes: Vec<RouteTableEntry>,
+}
+
+#[cfg(feature = "exts")]
+impl Deref for OwnedInterfaceInfo {
+    type Target = InterfaceInfo;
+
+    fn deref(&self) -> &InterfaceInfo {
+        &self.info
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
 pub struct RouteTableEntry {
     pub subnet_address: IPv4Address,
     pub subnet_mask: IPv4Address,
@@ -3,12 +3,17 @@
 use crate::{
     proto::Protocol, 
     unsafe_guid, 
-    proto::dhcp4::HardwareType,
+    Guid,
+    Handle,
+    Status,
+    data_types::{CStr16, Char16},
+    table::boot::BootServices,
+    proto::dhcp4::{HardwareType, MacAddress, Ipv4Addr},
     alloc::ALLOCATOR,
 };
 use core::{
     ptr,
-    mem::size_of,
+    mem::{align_of, size_of},
     convert::TryFrom,
 };
 use num_enum::{
@@ -19,8 +24,12 @@ use num_enum::{
 #[cfg(feature = "exts")]
 use alloc_api::{
     format,
+    string::String,
+    vec,
+    vec::Vec,
     alloc::{
         alloc,
+        handle_alloc_error,
         Layout,
         GlobalAlloc,
     },
@@ -28,18 +37,118 @@ use alloc_api::{
 
 // Memory management ==========================================================
 
-pub struct DevicePathBox { ptr: ptr::Unique<DevicePath> }
+/// An owned device path.
+///
+/// The path is freed to the UEFI pool when dropped, as is all memory of the
+/// global allocator, so paths allocated by the firmware from pool, such as
+/// those returned by `DevicePathUtilities`, are boxed too.
+pub struct DevicePathBox {
+    ptr: ptr::Unique<DevicePath>,
+    // layout the path was allocated with, covering every node
+    layout: Layout,
+    // frees the path when dropped
+    dealloc: unsafe fn(*mut u8, Layout),
+}
+
+unsafe fn dealloc_path(p: *mut u8, layout: Layout) {
+    ALLOCATOR.dealloc(p, layout);
+}
 
 impl DevicePathBox {
 
-    fn new(p: *mut DevicePath) -> DevicePathBox {
-        DevicePathBox{ ptr: unsafe { ptr::Unique::new_unchecked(p) } }
+    fn new(p: *mut DevicePath, layout: Layout) -> DevicePathBox {
+        DevicePathBox{
+            ptr: unsafe { ptr::Unique::new_unchecked(p) },
+            layout,
+            dealloc: dealloc_path,
+        }
     }
 
     pub fn as_ptr(&self) -> *const DevicePath {
         self.ptr.as_ptr()
     }
 
+    /// Returns the size in bytes of the allocation holding the path.
+    pub fn size(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// Replace the function the path is freed with when dropped, which is
+    /// passed the pointer and layout of the allocation, e.g. to check the
+    /// layout freed.
+    ///
+    /// # Safety
+    ///
+    /// `dealloc` must free the allocation to the global allocator, or take
+    /// ownership of it.
+    pub unsafe fn set_dealloc(&mut self, dealloc: unsafe fn(*mut u8, Layout)) {
+        self.dealloc = dealloc;
+    }
+
+    /// Check that the path is well formed.
+    ///
+    /// Parsing the allocation must find an end of entire path node within
+    /// it, and the nodes found must match those walked by `nodes`, which
+    /// trusts the lengths and end nodes of the path. The parse never reads
+    /// past the allocation, so a path missing its end node is reported.
+    pub fn self_check(&self) -> Result<(), DevicePathError> {
+
+        let (nodes, size) = unsafe { DevicePath::scan(self.as_ptr() as *const u8, self.size())? };
+
+        if self.nodes().count() != nodes || self.as_bytes().len() != size {
+            return Err(DevicePathError::Mismatch);
+        }
+
+        Ok(())
+
+    }
+
+    /// Checks that the path is terminated by an end of entire path node
+    /// within its allocation, which paths from other sources may not be.
+    ///
+    /// A path lacking a terminator is moved to a new allocation with an end
+    /// node following its last whole node. Fails if a node has an invalid
+    /// type or length, as the path can then not be repaired.
+    pub fn ensure_terminated(&mut self) -> Result<(), DevicePathError> {
+
+        let bytes = unsafe {
+            core::slice::from_raw_parts(self.as_ptr() as *const u8, self.size())
+        };
+
+        // length of the whole nodes held by the allocation, a node running
+        // past its end is dropped
+        let mut len = 0;
+        while let Some(header) = bytes.get(len..len + size_of::<DevicePath>()) {
+            match header[0] {
+                0x01..=0x05 | 0x7f => {},
+                _ => return Err(DevicePathError::InvalidNodeType),
+            }
+            let node_len = u16::from_le_bytes([header[2], header[3]]) as usize;
+            if node_len < size_of::<DevicePath>() {
+                return Err(DevicePathError::InvalidNodeLength);
+            }
+            if len + node_len > bytes.len() {
+                break;
+            }
+            if header[0] == DeviceType::End as u8
+                && header[1] == EndPathSubType::EndEntire as u8 {
+                return Ok(());
+            }
+            len += node_len;
+        }
+
+        unsafe {
+            let layout = path_layout(len + size_of::<DevicePath>());
+            let p = alloc_path(layout);
+            ptr::copy_nonoverlapping(bytes.as_ptr(), p, len);
+            DevicePath::stamp(p.add(len), DeviceType::End, EndPathSubType::EndEntire as u8, ());
+            *self = DevicePathBox::new(p as *mut DevicePath, layout);
+        }
+        debug_assert_eq!(self.self_check(), Ok(()));
+        Ok(())
+
+    }
+
 }
 
 impl core::ops::Deref for DevicePathBox {
@@ -66,11 +175,10 @@ impl Drop for DevicePathBox {
     fn drop(&mut self) {
         unsafe {
             ptr::drop_in_place(self.ptr.as_ptr());
-            ALLOCATOR.dealloc(
+            (self.dealloc)(
                 self.ptr.as_mut() as *mut _ as *mut u8, 
-                Layout::new::<DevicePath>(),
+                self.layout,
             );
-            //TODO drop the entire path
         }
     }
 
@@ -106,22 +214,63 @@ pub struct DevicePathPayload<T> {
 #[unsafe_guid("0379BE4E-D706-437d-B037-EDB82FB772A4")]
 #[derive(Protocol)]
 pub struct DevicePathUtilities {
-    get_device_path_state: usize,
-    duplicate_device_path: usize,
-    append_device_node: usize,
+    get_device_path_size: extern "efiapi" fn(
+        device_path: &DevicePath,
+    ) -> usize,
+
+    duplicate_device_path: extern "efiapi" fn(
+        device_path: &DevicePath,
+    ) -> *mut DevicePath,
 
     append_device_path: extern "efiapi" fn(
         src1: &DevicePath,
         src2: &DevicePath,
     ) -> *mut DevicePath,
 
+    append_device_node: extern "efiapi" fn(
+        device_path: &DevicePath,
+        device_node: &DevicePath,
+    ) -> *mut DevicePath,
+
+    append_device_path_instance: extern "efiapi" fn(
+        device_path: &DevicePath,
+        device_path_instance: &DevicePath,
+    ) -> *mut DevicePath,
+
+    get_next_device_path_instance: extern "efiapi" fn(
+        device_path_instance: &mut *const DevicePath,
+        device_path_instance_size: &mut usize,
+    ) -> *mut DevicePath,
+
+    is_device_path_multi_instance: extern "efiapi" fn(
+        device_path: &DevicePath,
+    ) -> bool,
 
-    append_device_path_instance: usize,
-    get_next_device_path_instance: usize,
-    is_device_path_multi_instance: usize,
-    create_device_node: usize,
+    create_device_node: extern "efiapi" fn(
+        node_type: DeviceType,
+        node_sub_type: u8,
+        node_length: u16,
+    ) -> *mut DevicePath,
 }
 
+/// Device Path to Text Protocol. Converts device paths and nodes to their
+/// text representation.
+#[repr(C)]
+#[unsafe_guid("8b843e20-8132-4852-90cc-551a4e4a7f1c")]
+#[derive(Protocol)]
+pub struct DevicePathToText {
+    convert_device_node_to_text: extern "efiapi" fn(
+        device_node: &DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> *mut Char16,
+
+    convert_device_path_to_text: extern "efiapi" fn(
+        device_path: &DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> *mut Char16,
+}
 
 // Type and sub-type enums ====================================================
 
@@ -144,6 +293,23 @@ pub enum DeviceType {
 }
 
 
+impl DeviceType {
+
+    /// Returns the values and names of the known sub-types of this device
+    /// type, e.g. to list valid choices when building a path by hand.
+    pub fn subtypes(&self) -> &'static [(u8, &'static str)] {
+        match self {
+            DeviceType::Hardware => HardwarePathSubType::ALL,
+            DeviceType::ACPI => ACPIPathSubType::ALL,
+            DeviceType::Messaging => MessagingPathSubType::ALL,
+            DeviceType::Media => MediaPathSubType::ALL,
+            DeviceType::BIOSBootSpec => BIOSBootSpecPathSubType::ALL,
+            DeviceType::End => EndPathSubType::ALL,
+        }
+    }
+
+}
+
 /// Sub-type identifier for an "End of Hardware" device path
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
@@ -230,17 +396,72 @@ pub enum BIOSBootSpecPathSubType {
     V1_01 = 1,
 }
 
+// Implement `name`, returning the name of a sub-type variant, and `ALL`,
+// listing the values and names of every variant.
+macro_rules! impl_sub_type_name {
+    ($ty:ident { $($variant:ident),* $(,)? }) => {
+        impl $ty {
+            /// Values and names of every known sub-type.
+            pub const ALL: &'static [(u8, &'static str)] =
+                &[$(($ty::$variant as u8, stringify!($variant)),)*];
+
+            /// Returns the name of the sub-type.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($ty::$variant => stringify!($variant),)*
+                }
+            }
+        }
+    };
+}
+
+impl_sub_type_name!(EndPathSubType { EndInstance, EndEntire });
+impl_sub_type_name!(HardwarePathSubType { PCI, PCCARD, MemoryMapped, Vendor, Controller, BMC });
+impl_sub_type_name!(ACPIPathSubType { ACPI, ExpandedACPI, ADR });
+impl_sub_type_name!(MessagingPathSubType {
+    ATAPI, SCSI, FibreChannel, IEEE1394, USB, I20, InfiniBand, Vendor, MAC, IPv4, IPv6, UART,
+    USBClass, WWID, LogicalUnit, SATA, ISCSI, VLAN, FiberChannelEx, SASEx, NVME, URI, UFS, SD,
+    Bluetooth, Wireless, EMMC,
+});
+impl_sub_type_name!(MediaPathSubType {
+    HardDrive, CDROM, Vendor, File, MediaProtocol, PWIGFirmware, PWIGFirmwareVolume,
+    RelativeOffsetRange, RAMDisk,
+});
+impl_sub_type_name!(BIOSBootSpecPathSubType { V1_01 });
+
 // Device path implementation =================================================
 
+// Layout of a serialized path of `size` bytes. Device paths are byte aligned,
+// but the alignment passed to the allocator must still be a nonzero power of
+// two.
+fn path_layout(size: usize) -> Layout {
+    Layout::from_size_align(size, align_of::<DevicePath>())
+        .expect("Device path too large to allocate")
+}
+
+// Allocate storage for a serialized path, which must later be freed with the
+// same layout.
+unsafe fn alloc_path(layout: Layout) -> *mut u8 {
+
+    let p = alloc(layout);
+    if p.is_null() {
+        handle_alloc_error(layout);
+    }
+    p
+
+}
+
 impl DevicePath {
-    /// Create a new device path
+    /// Create a new device path of a single node, terminated by an end of
+    /// entire path node like every path this module constructs.
     pub fn new<T: Payload>(device_type: DeviceType, sub_type: u8, data: T) -> DevicePathBox {
 
-        let sz = size_of::<DevicePath>() + data.len();
+        // the node followed by the path ending
+        let xl = size_of::<DevicePath>() + data.len();
+        let sz = xl + size_of::<DevicePath>();
         unsafe {
-            let p = ptr::NonNull::new_unchecked(
-                alloc(Layout::from_size_align_unchecked(sz, 0))
-            );
+            let layout = path_layout(sz);
+            let p = ptr::NonNull::new_unchecked(alloc_path(layout));
 
             /*
             // device type
@@ -260,8 +481,16 @@ impl DevicePath {
             ptr::write(p.as_ptr().add(off).cast(), data);
             */
             Self::stamp(p.as_ptr(), device_type, sub_type, data);
+            Self::stamp(
+                p.as_ptr().add(xl),
+                DeviceType::End,
+                EndPathSubType::EndEntire as u8,
+                (),
+            );
 
-            DevicePathBox::new(p.as_ptr() as *mut DevicePath)
+            let path = DevicePathBox::new(p.as_ptr() as *mut DevicePath, layout);
+            debug_assert_eq!(path.self_check(), Ok(()));
+            path
         }
 
     }
@@ -272,9 +501,8 @@ impl DevicePath {
         let xl = x.len();
         let sz = x.len() + size_of::<DevicePath>();
         unsafe {
-            let p = ptr::NonNull::new_unchecked(
-                alloc(Layout::from_size_align_unchecked(sz, 0))
-            );
+            let layout = path_layout(sz);
+            let p = ptr::NonNull::new_unchecked(alloc_path(layout));
 
             Self::stamp(p.as_ptr(), x.path.device_type, x.path.sub_type, x.payload);
             Self::stamp(
@@ -284,7 +512,9 @@ impl DevicePath {
                 (),
             );
 
-            DevicePathBox::new(p.as_ptr() as *mut DevicePath)
+            let path = DevicePathBox::new(p.as_ptr() as *mut DevicePath, layout);
+            debug_assert_eq!(path.self_check(), Ok(()));
+            path
         }
 
     }
@@ -296,9 +526,8 @@ impl DevicePath {
         let yl = y.len();
         let sz = xl + yl + size_of::<DevicePath>();
         unsafe {
-            let p = ptr::NonNull::new_unchecked(
-                alloc(Layout::from_size_align_unchecked(sz, 0))
-            );
+            let layout = path_layout(sz);
+            let p = ptr::NonNull::new_unchecked(alloc_path(layout));
 
             Self::stamp(p.as_ptr(), x.path.device_type, x.path.sub_type, x.payload);
             Self::stamp(
@@ -314,42 +543,46 @@ impl DevicePath {
                 (),
             );
 
-            DevicePathBox::new(p.as_ptr() as *mut DevicePath)
+            let path = DevicePathBox::new(p.as_ptr() as *mut DevicePath, layout);
+            debug_assert_eq!(path.self_check(), Ok(()));
+            path
         }
 
     }
 
+    /// Returns a new path made of the nodes of `a`, without its end node,
+    /// followed by the nodes of `b`, including its end node.
     pub fn append(a: &DevicePath, b: &DevicePath) -> DevicePathBox {
 
+        // all of a except its final node, which ends the path
         let mut alen = 0usize;
-        let mut blen = 0usize;
+        let mut aend = 0usize;
+        a.walk(&mut |x| { alen += x.len(); aend = x.len(); });
+        let head = alen - aend;
 
-        a.walk(&mut |x| alen += x.len());
+        let mut blen = 0usize;
         b.walk(&mut |x| blen += x.len());
 
-        let len = alen + blen - size_of::<DevicePath>();
+        let len = head + blen;
 
         unsafe {
-            let p = ptr::NonNull::new_unchecked(
-                alloc(Layout::from_size_align_unchecked(len, 0))
-            );
+            let layout = path_layout(len);
+            let p = ptr::NonNull::new_unchecked(alloc_path(layout));
 
-            let off = alen - size_of::<DevicePath>();
             ptr::copy_nonoverlapping(
                 a as *const _ as *const u8,
                 p.as_ptr(),
-                off,
+                head,
             );
             ptr::copy_nonoverlapping(
                 b as *const _ as *const u8,
-                p.as_ptr().add(off),
+                p.as_ptr().add(head),
                 blen,
             );
 
-            DevicePathBox::new(p.as_ptr() as *mut DevicePath)
+            DevicePathBox::new(p.as_ptr() as *mut DevicePath, layout)
         }
 
-
     }
 
     fn stamp<T: Payload>(p: *mut u8, device_type: DeviceType, sub_type: u8, data: T) {
@@ -390,16 +623,54 @@ impl DevicePath {
 
     }
 
+    /// Returns the name of the sub-type of this node, decoded according to
+    /// its device type, or `None` if the sub-type is unknown.
+    pub fn sub_type_name(&self) -> Option<&'static str> {
+
+        let sub_type = self.sub_type;
+        match self.device_type {
+            DeviceType::Hardware =>
+                HardwarePathSubType::try_from(sub_type).ok().map(HardwarePathSubType::name),
+            DeviceType::ACPI =>
+                ACPIPathSubType::try_from(sub_type).ok().map(ACPIPathSubType::name),
+            DeviceType::Messaging =>
+                MessagingPathSubType::try_from(sub_type).ok().map(MessagingPathSubType::name),
+            DeviceType::Media =>
+                MediaPathSubType::try_from(sub_type).ok().map(MediaPathSubType::name),
+            DeviceType::BIOSBootSpec =>
+                BIOSBootSpecPathSubType::try_from(sub_type).ok().map(BIOSBootSpecPathSubType::name),
+            DeviceType::End =>
+                EndPathSubType::try_from(sub_type).ok().map(EndPathSubType::name),
+        }
+
+    }
+
+    /// Returns the data of this node following its header, as given by the
+    /// node length.
+    pub fn payload_bytes(&self) -> &[u8] {
+
+        let len = self.len().saturating_sub(size_of::<DevicePath>());
+        unsafe {
+            let p = (self as *const Self as *const u8).add(size_of::<DevicePath>());
+            core::slice::from_raw_parts(p, len)
+        }
+
+    }
+
     pub fn walk(&self, f: &mut dyn FnMut(&DevicePath)) {
 
-        f(self);
-        match self.next() {
-            Some(x) => { x.walk(f) }
-            None => { }
+        for node in self.nodes() {
+            f(node);
         }
 
     }
 
+    /// Iterate over the nodes of the path, from this node up to and
+    /// including the node ending the entire path.
+    pub fn nodes(&self) -> DevicePathNodes<'_> {
+        DevicePathNodes(Some(self))
+    }
+
     pub fn next(&self) -> Option<&DevicePath> {
 
         if self.device_type == DeviceType::End {
@@ -435,150 +706,1442 @@ impl DevicePath {
 
     pub fn len(&self) -> usize { u16::from_le_bytes(self.length) as usize }
 
-}
+    /// Returns the serialized path, from this node up to and including the
+    /// node ending the path.
+    pub fn as_bytes(&self) -> &[u8] {
 
-impl<T: Payload> DevicePathPayload<T> {
+        let mut size = 0usize;
+        self.walk(&mut |x| size += x.len());
 
-    pub fn create(device_type: DeviceType, sub_type: u8, payload: T) -> Self {
+        unsafe {
+            core::slice::from_raw_parts(self as *const _ as *const u8, size)
+        }
 
-        DevicePathPayload{
-            path: DevicePath {
-                device_type: device_type,
-                sub_type: sub_type,
-                length: ((size_of::<DevicePath>() + payload.len()) as u16).to_le_bytes(),
-            },
-            payload: payload,
+    }
+
+    /// Returns the GPT partition GUID of the first hard drive node in the
+    /// path, if any.
+    pub fn partition_guid(&self) -> Option<Guid> {
+
+        let mut guid = None;
+        self.walk(&mut |x| {
+            if guid.is_none()
+                && x.device_type == DeviceType::Media
+                && x.sub_type == MediaPathSubType::HardDrive as u8
+                && x.len() >= size_of::<DevicePath>() + size_of::<HardDriveDevicePath>() {
+                guid = x.payload::<HardDriveDevicePath>().gpt_guid();
+            }
+        });
+        guid
+
+    }
+
+    /// Returns true if both paths refer to the same partition.
+    ///
+    /// Paths containing a GPT hard drive node are compared by partition GUID,
+    /// so a short-form path starting at the hard drive node matches the full
+    /// path of the same partition. Other paths must be byte for byte equal.
+    pub fn refers_to_same_partition(&self, other: &DevicePath) -> bool {
+
+        match (self.partition_guid(), other.partition_guid()) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.as_bytes() == other.as_bytes(),
         }
 
     }
 
-    pub fn len(&self) -> usize {
-        u16::from_le_bytes(self.path.length) as usize
+    /// Returns true if the path holds the vendor media node the Linux EFI
+    /// stub uses to locate the `LoadFile2` protocol serving its initrd.
+    ///
+    /// The node is searched anywhere in the path, so that it is also found
+    /// behind the nodes of the device providing it.
+    pub fn is_linux_initrd(&self) -> bool {
+
+        self.nodes().any(|node| {
+            node.device_type == DeviceType::Media
+                && node.sub_type == MediaPathSubType::Vendor as u8
+                && matches!(node.vendor_data(), Some((guid, _)) if guid == LINUX_INITRD_MEDIA_GUID)
+        })
+
     }
 
+    /// Returns the GUID and the vendor defined data of a hardware, messaging
+    /// or media vendor node, or `None` for other nodes.
+    pub fn vendor_data(&self) -> Option<(Guid, &[u8])> {
 
-}
+        let vendor = match self.device_type {
+            DeviceType::Hardware => self.sub_type == HardwarePathSubType::Vendor as u8,
+            DeviceType::Messaging => self.sub_type == MessagingPathSubType::Vendor as u8,
+            DeviceType::Media => self.sub_type == MediaPathSubType::Vendor as u8,
+            _ => false,
+        };
 
-// Device path utilities implementation =======================================
+        let payload = self.payload_bytes();
+        if !vendor || payload.len() < size_of::<Guid>() {
+            return None;
+        }
 
-impl DevicePathUtilities {
+        let guid = unsafe { ptr::read_unaligned(payload.as_ptr() as *const Guid) };
+        Some((guid, &payload[size_of::<Guid>()..]))
 
-   pub fn append_device_path(
-       &self, src1: &DevicePath, src2: &DevicePath) -> *mut DevicePath {
+    }
 
-       (self.append_device_path)(src1, src2)
+    /// Returns the serialized path as hex bytes, one node per line, with the
+    /// four header bytes of each node set apart from its payload.
+    ///
+    /// For example a PCI node followed by the end node dumps as
+    /// `01 01 06 00  00 1f` and `7f ff 04 00` on separate lines.
+    #[cfg(feature = "exts")]
+    pub fn hex_dump(&self) -> String {
 
-   }
+        use core::fmt::Write;
 
-}
+        let mut dump = String::new();
+        for node in self.nodes() {
+            if !dump.is_empty() {
+                dump.push('\n');
+            }
+            let bytes = unsafe {
+                core::slice::from_raw_parts(node as *const _ as *const u8, node.len())
+            };
+            for (i, b) in bytes.iter().enumerate() {
+                let sep = match i {
+                    0 => "",
+                    4 => "  ",
+                    _ => " ",
+                };
+                let _ = write!(dump, "{}{:02x}", sep, b);
+            }
+        }
+        dump
 
-// Payloads ===================================================================
+    }
 
-// Common traits all payload types must implement, necessitated by the fact
-// that payloads have a packed representation but not all payloads are
-// necessarily sized at compile time.
-pub trait Payload {
+    /// Returns a hash of the serialized path, usable as a cache key.
+    ///
+    /// The hash is a 64 bit FNV-1a over the nodes, so it is stable across
+    /// runs on the same firmware. The sub-type of end nodes is left out, so a
+    /// path hashes the same whether it ends the entire path or an instance.
+    pub fn stable_hash(&self) -> u64 {
+
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let mut hash = OFFSET_BASIS;
+        let mut feed = |bytes: &[u8]| {
+            for &b in bytes {
+                hash ^= b as u64;
+                hash = hash.wrapping_mul(PRIME);
+            }
+        };
 
-    fn len(&self) -> usize;
-    fn ptr(&self) -> *const u8;
+        self.walk(&mut |x| {
+            let bytes = unsafe {
+                core::slice::from_raw_parts(x as *const _ as *const u8, x.len())
+            };
+            if x.device_type == DeviceType::End {
+                feed(&bytes[..1]);
+                feed(&bytes[2..]);
+            } else {
+                feed(bytes);
+            }
+        });
+        hash
 
-}
+    }
 
-impl Payload for () {
+    /// Returns the VLAN id of the first VLAN node in the path, or `None` if
+    /// the path is untagged.
+    pub fn vlan_id(&self) -> Option<u16> {
+
+        let mut id = None;
+        self.walk(&mut |x| {
+            if id.is_none()
+                && x.device_type == DeviceType::Messaging
+                && x.sub_type == MessagingPathSubType::VLAN as u8
+                && x.len() >= size_of::<DevicePath>() + size_of::<VLANDevicePath>() {
+                id = Some(x.payload::<VLANDevicePath>().vlan_id());
+            }
+        });
+        id
 
-    fn len(&self) -> usize { 0 }
-    fn ptr(&self) -> *const u8 { ptr::null() }
+    }
 
-}
+    /// Validate a serialized device path, returning the path it holds.
+    ///
+    /// Each node must lie within `bytes` and the path must be terminated by
+    /// an end of entire path node. Bytes following that node are ignored.
+    pub fn parse(bytes: &[u8]) -> Result<&DevicePath, DevicePathError> {
 
-/// This Device Path contains ACPI Device IDs that represent a device’s Plug
-/// and Play Hardware ID and its corresponding unique persistent ID. The ACPI
-/// IDs are stored in the ACPI _HID, _CID, and _UID device identification
-/// objects that are associated with a device. The ACPI Device Path contains
-/// values that must match exactly the ACPI name space that is provided by the
-/// platform firmware to the operating system. Refer to the ACPI specification
-/// for a complete description of the.  _HID, _CID, and _UID device
-/// identification objects.
-#[repr(C)]
-pub struct ACPIDevicePath {
-    /// Device’s PnP hardware ID stored in a numeric 32-bit
-    /// compressed EISA-type ID. This value must match the
-    /// corresponding _HID in the ACPI name space.
-    pub hid: u32,
-    /// Unique ID that is required by ACPI if two devices have the
-    /// same _HID. This value must also match the corresponding
-    /// _UID/_HID pair in the ACPI name space. Only the 32-bit
-    /// numeric value type of _UID is supported; thus strings must
-    /// not be used for the _UID in the ACPI name space.
-    pub uid: u32,
-}
+        unsafe { Self::scan(bytes.as_ptr(), bytes.len())?; }
+        Ok(unsafe { &*(bytes.as_ptr() as *const DevicePath) })
 
-impl Payload for ACPIDevicePath {
+    }
 
-    fn len(&self) -> usize { size_of::<Self>() }
-    fn ptr(&self) -> *const u8 { &self.hid as *const _ as *const u8 }
-}
+    /// Count the nodes and total size of the path at `p`, up to and
+    /// including its end of entire path node, reading at most `max` bytes.
+    unsafe fn scan(p: *const u8, max: usize) -> Result<(usize, usize), DevicePathError> {
 
-#[repr(C)]
-pub struct MACDevicePath {
-    pub address: [u8;32],
-    pub iftype: HardwareType,
-}
+        let mut nodes = 0;
+        let mut offset = 0;
 
-impl Payload for MACDevicePath {
+        loop {
+            if offset + size_of::<DevicePath>() > max {
+                return Err(DevicePathError::MissingEnd);
+            }
 
-    fn len(&self) -> usize { size_of::<Self>() }
-    fn ptr(&self) -> *const u8 { &self.address as *const _ as *const u8 }
-}
+            let node = p.add(offset);
+            let device_type = *node;
+            let sub_type = *node.add(1);
+            let len = u16::from_le_bytes([*node.add(2), *node.add(3)]) as usize;
 
-#[repr(C)]
-pub struct PCIDevicePath {
-    pub function: u8,
-    pub device: u8,
-}
+            match device_type {
+                0x01..=0x05 | 0x7f => {},
+                _ => return Err(DevicePathError::InvalidNodeType),
+            }
+            if len < size_of::<DevicePath>() {
+                return Err(DevicePathError::InvalidNodeLength);
+            }
+            if offset + len > max {
+                return Err(DevicePathError::MissingEnd);
+            }
 
-impl Payload for PCIDevicePath {
+            nodes += 1;
+            offset += len;
+
+            if device_type == DeviceType::End as u8
+                && sub_type == EndPathSubType::EndEntire as u8 {
+                return Ok((nodes, offset));
+            }
+        }
+
+    }
+
+    /// Returns a view of the first instance of a multi-instance path, such as
+    /// the ConOut variable. Walking the view stops at the first end node
+    /// rather than continuing into the next instance.
+    pub fn first_instance(&self) -> DevicePathInstance<'_> {
+        DevicePathInstance(self)
+    }
 
-    fn len(&self) -> usize { size_of::<Self>() }
-    fn ptr(&self) -> *const u8 { &self.function as *const _ as *const u8 }
 }
 
-#[repr(C)]
-pub struct IPv4DevicePath {
-    pub local_ip: [u8;4],
-    pub remote_ip: [u8;4],
-    pub local_port: u16,
-    pub remote_port: u16,
-    pub protocol: IPProtocol,
-    pub static_ip: StaticIPAddr,
-    pub gateway_ip: [u8;4],
-    pub subnet_mask: [u8;4],
+/// Builds a device path node by node.
+///
+/// Node lengths are computed from the payloads, and `finish` terminates the
+/// path with a single end of entire path node. Unlike `DevicePath::new2`,
+/// any number of nodes can be pushed, e.g. for an HTTP boot path:
+///
+/// ```ignore
+/// let path = DevicePathBuilder::new()
+///     .push(DeviceType::Messaging, MessagingPathSubType::MAC as u8, MACDevicePath::from(mac))
+///     .push(
+///         DeviceType::Messaging,
+///         MessagingPathSubType::IPv4 as u8,
+///         IPv4DevicePath::new(Ipv4Addr::default(), 0, IPProtocol::TCP),
+///     )
+///     .push(
+///         DeviceType::Messaging,
+///         MessagingPathSubType::URI as u8,
+///         URIDevicePath{ uri: "http://192.168.1.1/boot.efi" },
+///     )
+///     .finish();
+/// ```
+#[cfg(feature = "exts")]
+#[derive(Default)]
+pub struct DevicePathBuilder {
+    buf: Vec<u8>,
 }
 
-impl Payload for IPv4DevicePath {
+#[cfg(feature = "exts")]
+impl DevicePathBuilder {
 
-    fn len(&self) -> usize { size_of::<Self>() }
-    fn ptr(&self) -> *const u8 { &self.local_ip as *const _ as *const u8 }
+    /// Create a builder for an empty path.
+    pub fn new() -> DevicePathBuilder {
+        DevicePathBuilder{ buf: Vec::new() }
+    }
 
-}
+    /// Append a node carrying `payload`.
+    pub fn push<T: Payload>(self, device_type: DeviceType, sub_type: u8, payload: T) -> Self {
 
-#[repr(C)]
-pub struct URIDevicePath {
-    pub uri: &'static str, //TODO probably non static lifetime better
-}
+        let data = if payload.len() == 0 {
+            &[][..]
+        } else {
+            unsafe { core::slice::from_raw_parts(payload.ptr(), payload.len()) }
+        };
+        self.push_bytes(device_type, sub_type, data)
 
-impl Payload for URIDevicePath {
+    }
 
-    fn len(&self) -> usize { self.uri.len() }
-    fn ptr(&self) -> *const u8 { self.uri as *const _ as *const u8 }
+    /// Append a node carrying the raw bytes `data`, for nodes with a variable
+    /// length payload.
+    pub fn push_bytes(mut self, device_type: DeviceType, sub_type: u8, data: &[u8]) -> Self {
+
+        let len = (size_of::<DevicePath>() + data.len()) as u16;
+        self.buf.push(device_type as u8);
+        self.buf.push(sub_type);
+        self.buf.extend_from_slice(&len.to_le_bytes());
+        self.buf.extend_from_slice(data);
+        self
+
+    }
+
+    /// Terminate the path and copy it into a `DevicePathBox`.
+    pub fn finish(self) -> DevicePathBox {
+
+        let mut buf = self.push_bytes(DeviceType::End, EndPathSubType::EndEntire as u8, &[]).buf;
+
+        let path = unsafe {
+            let layout = path_layout(buf.len());
+            let p = alloc_path(layout);
+            ptr::copy_nonoverlapping(buf.as_mut_ptr(), p, buf.len());
+            DevicePathBox::new(p as *mut DevicePath, layout)
+        };
+        debug_assert_eq!(path.self_check(), Ok(()));
+        path
+
+    }
+
+    /// Start a path to a RAM disk holding an image, e.g. an ISO downloaded
+    /// to memory, from `start` to `end`, the address of its last byte.
+    pub fn ram_disk(start: u64, end: u64, kind: RamDiskKind) -> DevicePathBuilder {
+
+        DevicePathBuilder::new().push(
+            DeviceType::Media,
+            MediaPathSubType::RAMDisk as u8,
+            RamDiskDevicePath::new(start, end, kind),
+        )
+
+    }
+
+    /// Append a file path node, pointing at `path` within the device of the
+    /// preceding nodes. Fails if `path` holds characters outside of UCS-2.
+    pub fn with_file(self, path: &str) -> crate::Result<Self> {
+
+        let file = FileDevicePath::new(path)?.log();
+        Ok(self.push(DeviceType::Media, MediaPathSubType::File as u8, file).into())
+
+    }
+
+    /// Build the `MAC/IPv4/URI` path of an HTTP boot entry, downloading
+    /// `uri` over the interface with address `mac`, configured by DHCP.
+    pub fn http_boot(mac: MacAddress, uri: &str) -> DevicePathBox {
+
+        DevicePathBuilder::new()
+            .push(DeviceType::Messaging, MessagingPathSubType::MAC as u8, MACDevicePath::from(mac))
+            .push(
+                DeviceType::Messaging,
+                MessagingPathSubType::IPv4 as u8,
+                IPv4DevicePath::new(Ipv4Addr::default(), 0, IPProtocol::TCP),
+            )
+            .push_bytes(DeviceType::Messaging, MessagingPathSubType::URI as u8, uri.as_bytes())
+            .finish()
+
+    }
+
+    /// Build the `MAC/IPv4/iSCSI` path of a SAN boot entry, for the target
+    /// named `iqn` reached at `target_ip` on the standard iSCSI port.
+    pub fn iscsi(mac: MacAddress, target_ip: Ipv4Addr, iqn: &str, lun: [u8;8]) -> DevicePathBox {
+
+        DevicePathBuilder::new()
+            .push(DeviceType::Messaging, MessagingPathSubType::MAC as u8, MACDevicePath::from(mac))
+            .push(
+                DeviceType::Messaging,
+                MessagingPathSubType::IPv4 as u8,
+                IPv4DevicePath::new(target_ip, ISCSI_PORT, IPProtocol::TCP),
+            )
+            .push(
+                DeviceType::Messaging,
+                MessagingPathSubType::ISCSI as u8,
+                IscsiDevicePath::new(lun, iqn),
+            )
+            .finish()
+
+    }
 
 }
 
-#[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
-pub enum  StaticIPAddr {
-    DHCP = 0,
-    Static = 1,
+/// Well known TCP port of iSCSI targets.
+pub const ISCSI_PORT: u16 = 3260;
+
+/// Errors found when validating a device path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DevicePathError {
+    /// A node has an unknown device type.
+    InvalidNodeType,
+    /// A node is shorter than the node header.
+    InvalidNodeLength,
+    /// The path is not terminated by an end of entire path node.
+    MissingEnd,
+    /// Parsing the path finds other nodes than walking it.
+    Mismatch,
+}
+
+/// A single instance of a device path, see `DevicePath::first_instance`.
+#[derive(Clone, Copy)]
+pub struct DevicePathInstance<'a>(&'a DevicePath);
+
+impl<'a> DevicePathInstance<'a> {
+
+    /// Returns the first node of the instance.
+    pub fn head(&self) -> &'a DevicePath { self.0 }
+
+    /// Calls `f` on each node of the instance, not including the end node
+    /// that terminates it.
+    pub fn walk(&self, f: &mut dyn FnMut(&'a DevicePath)) {
+
+        let mut node = self.0;
+        while node.device_type != DeviceType::End {
+            f(node);
+            match node.next() {
+                Some(x) => node = x,
+                None => break,
+            }
+        }
+
+    }
+
+    /// Returns the serialized nodes of the instance, without the end node.
+    pub fn as_bytes(&self) -> &'a [u8] {
+
+        let mut size = 0usize;
+        self.walk(&mut |x| size += x.len());
+
+        unsafe {
+            core::slice::from_raw_parts(self.0 as *const _ as *const u8, size)
+        }
+
+    }
+
+}
+
+/// Iterator over the nodes of a device path, see `DevicePath::nodes`.
+#[derive(Clone, Copy)]
+pub struct DevicePathNodes<'a>(Option<&'a DevicePath>);
+
+impl<'a> Iterator for DevicePathNodes<'a> {
+
+    type Item = &'a DevicePath;
+
+    fn next(&mut self) -> Option<&'a DevicePath> {
+
+        let node = self.0?;
+        self.0 = node.next();
+        Some(node)
+
+    }
+
+}
+
+/// Broad category of boot device a device path refers to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BootCategory {
+    /// An internal disk, such as a SATA or NVMe drive.
+    LocalDisk,
+    /// An optical disc.
+    Optical,
+    /// A network boot source.
+    Network,
+    /// A removable device, such as a USB drive.
+    Removable,
+    /// Anything else.
+    Other,
+}
+
+impl DevicePath {
+
+    /// Classify the boot device this path refers to.
+    ///
+    /// When a path contains nodes of several categories, network nodes take
+    /// precedence, followed by optical media, removable media and local
+    /// disks.
+    pub fn boot_category(&self) -> BootCategory {
+
+        let (mut network, mut optical, mut removable, mut disk) =
+            (false, false, false, false);
+
+        self.walk(&mut |node| {
+            match node.device_type {
+                DeviceType::Messaging => {
+                    match MessagingPathSubType::try_from(node.sub_type) {
+                        Ok(MessagingPathSubType::MAC)
+                        | Ok(MessagingPathSubType::IPv4)
+                        | Ok(MessagingPathSubType::IPv6)
+                        | Ok(MessagingPathSubType::URI)
+                        | Ok(MessagingPathSubType::ISCSI) => network = true,
+                        Ok(MessagingPathSubType::USB)
+                        | Ok(MessagingPathSubType::USBClass) => removable = true,
+                        Ok(MessagingPathSubType::SATA)
+                        | Ok(MessagingPathSubType::NVME)
+                        | Ok(MessagingPathSubType::SCSI)
+                        | Ok(MessagingPathSubType::ATAPI) => disk = true,
+                        _ => {}
+                    }
+                }
+                DeviceType::Media => {
+                    match MediaPathSubType::try_from(node.sub_type) {
+                        Ok(MediaPathSubType::CDROM) => optical = true,
+                        Ok(MediaPathSubType::HardDrive) => disk = true,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        });
+
+        if network {
+            BootCategory::Network
+        } else if optical {
+            BootCategory::Optical
+        } else if removable {
+            BootCategory::Removable
+        } else if disk {
+            BootCategory::LocalDisk
+        } else {
+            BootCategory::Other
+        }
+
+    }
+
+    /// Returns true if the path refers to internal storage: an NVMe, SATA or
+    /// eMMC device reached from a PCI root bridge through PCI devices only,
+    /// optionally followed by a partition and a file.
+    ///
+    /// Paths holding any other node, such as USB, network or RAM disk nodes,
+    /// or holding several instances, are not internal storage.
+    pub fn is_internal_storage(&self) -> bool {
+
+        let mut nodes = self.nodes();
+
+        // the path must start at a PCI root bridge
+        match nodes.next() {
+            Some(node) if node.device_type == DeviceType::ACPI => {}
+            _ => return false,
+        }
+
+        let (mut pci, mut storage) = (false, false);
+        for node in nodes {
+            match node.device_type {
+                DeviceType::Hardware
+                    if !storage && node.sub_type == HardwarePathSubType::PCI as u8 =>
+                    pci = true,
+                DeviceType::Messaging
+                    if pci && !storage && matches!(
+                        MessagingPathSubType::try_from(node.sub_type),
+                        Ok(MessagingPathSubType::NVME)
+                        | Ok(MessagingPathSubType::SATA)
+                        | Ok(MessagingPathSubType::EMMC)
+                    ) =>
+                    storage = true,
+                DeviceType::Media
+                    if storage && matches!(
+                        MediaPathSubType::try_from(node.sub_type),
+                        Ok(MediaPathSubType::HardDrive) | Ok(MediaPathSubType::File)
+                    ) => {}
+                DeviceType::End if node.sub_type == EndPathSubType::EndEntire as u8 =>
+                    return storage,
+                _ => return false,
+            }
+        }
+        false
+
+    }
+
+}
+
+/// Vendor GUID of the media device path the Linux EFI stub loads its initrd
+/// from.
+pub const LINUX_INITRD_MEDIA_GUID: Guid = Guid::from_values(
+    0x5568e427,
+    0x68fc,
+    0x4f3d,
+    0xac74,
+    [0xca, 0x55, 0x52, 0x31, 0xcc, 0x68],
+);
+
+impl<T: Payload> DevicePathPayload<T> {
+
+    pub fn create(device_type: DeviceType, sub_type: u8, payload: T) -> Self {
+
+        DevicePathPayload{
+            path: DevicePath {
+                device_type: device_type,
+                sub_type: sub_type,
+                length: ((size_of::<DevicePath>() + payload.len()) as u16).to_le_bytes(),
+            },
+            payload: payload,
+        }
+
+    }
+
+    pub fn len(&self) -> usize {
+        u16::from_le_bytes(self.path.length) as usize
+    }
+
+
+}
+
+// Device path utilities implementation =======================================
+
+impl DevicePathUtilities {
+
+    /// Returns a new path made of `src1` without its end node, followed by
+    /// `src2`, allocated by the firmware.
+    pub fn append_device_path(
+        &self, src1: &DevicePath, src2: &DevicePath) -> DevicePathBox {
+
+        let size = src1.as_bytes().len() + src2.as_bytes().len() - size_of::<DevicePath>();
+        let layout = path_layout(size);
+        let p = (self.append_device_path)(src1, src2);
+        if p.is_null() {
+            handle_alloc_error(layout);
+        }
+        DevicePathBox::new(p, layout)
+
+    }
+
+    /// Returns the size of `path` in bytes, including the node ending it, as
+    /// computed by the firmware.
+    pub fn device_path_size(&self, path: &DevicePath) -> usize {
+
+        (self.get_device_path_size)(path)
+
+    }
+
+    /// Returns a new path made of `path` followed by the single node `node`,
+    /// terminated by a new end node. Nodes following `node` are ignored.
+    pub fn append_node(&self, path: &DevicePath, node: &DevicePath) -> DevicePathBox {
+
+        let layout = path_layout(path.as_bytes().len() + node.len());
+        let p = (self.append_device_node)(path, node);
+        if p.is_null() {
+            handle_alloc_error(layout);
+        }
+        DevicePathBox::new(p, layout)
+
+    }
+
+    /// Returns a new path made of the instances of `path` followed by
+    /// `instance`, separated by an end of instance node.
+    pub fn append_instance(&self, path: &DevicePath, instance: &DevicePath) -> DevicePathBox {
+
+        let layout = path_layout(path.as_bytes().len() + instance.as_bytes().len());
+        let p = (self.append_device_path_instance)(path, instance);
+        if p.is_null() {
+            handle_alloc_error(layout);
+        }
+        DevicePathBox::new(p, layout)
+
+    }
+
+    /// Returns true if `path` holds more than one instance.
+    pub fn is_multi_instance(&self, path: &DevicePath) -> bool {
+
+        (self.is_device_path_multi_instance)(path)
+
+    }
+
+    /// Iterate over copies of the instances of `path`, each terminated by an
+    /// end of entire path node.
+    pub fn instances<'a>(&'a self, path: &'a DevicePath) -> InstanceIter<'a> {
+        InstanceIter{ utilities: self, next: path }
+    }
+
+    /// Returns a single node, allocated by the firmware, carrying `data`.
+    ///
+    /// The node is not followed by an end node, so it is not a path by
+    /// itself. An empty `data` creates a node made of its header only.
+    pub fn create_node(
+        &self, device_type: DeviceType, sub_type: u8, data: &[u8]) -> DevicePathBox {
+
+        let len = size_of::<DevicePath>() + data.len();
+        assert!(len <= u16::MAX as usize, "Device path node too large");
+
+        let layout = path_layout(len);
+        let p = (self.create_device_node)(device_type, sub_type, len as u16);
+        if p.is_null() {
+            handle_alloc_error(layout);
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                (p as *mut u8).add(size_of::<DevicePath>()),
+                data.len(),
+            );
+        }
+        DevicePathBox::new(p, layout)
+
+    }
+
+    /// Returns an owned copy of `src`, e.g. of a path borrowed from a handle.
+    ///
+    /// The copy is allocated from pool by the firmware, and freed back to the
+    /// pool when the box is dropped.
+    pub fn duplicate(&self, src: &DevicePath) -> DevicePathBox {
+
+        let layout = path_layout(src.as_bytes().len());
+        let p = (self.duplicate_device_path)(src);
+        if p.is_null() {
+            handle_alloc_error(layout);
+        }
+        DevicePathBox::new(p, layout)
+
+    }
+
+}
+
+/// Iterator over the instances of a device path, see
+/// `DevicePathUtilities::instances`.
+pub struct InstanceIter<'a> {
+    utilities: &'a DevicePathUtilities,
+    // next instance to copy, null once the end of the entire path was reached
+    next: *const DevicePath,
+}
+
+impl<'a> Iterator for InstanceIter<'a> {
+
+    type Item = DevicePathBox;
+
+    fn next(&mut self) -> Option<DevicePathBox> {
+
+        if self.next.is_null() {
+            return None;
+        }
+
+        let mut size = 0usize;
+        let p = (self.utilities.get_next_device_path_instance)(&mut self.next, &mut size);
+        if p.is_null() {
+            self.next = ptr::null();
+            return None;
+        }
+        Some(DevicePathBox::new(p, path_layout(size)))
+
+    }
+
+}
+
+impl DevicePathToText {
+
+    /// Converts `path` to text, allocated by the firmware from pool. Returns
+    /// null if the text could not be allocated.
+    ///
+    /// `display_only` selects the shorter, but not parseable, display form
+    /// and `allow_shortcuts` the shortcut forms of nodes, such as `IPv4()`.
+    pub fn convert_device_path_to_text(
+        &self, path: &DevicePath, display_only: bool, allow_shortcuts: bool) -> *mut Char16 {
+
+        (self.convert_device_path_to_text)(path, display_only, allow_shortcuts)
+
+    }
+
+    /// Converts the single node `node` to text, allocated by the firmware
+    /// from pool. Returns null if the text could not be allocated.
+    pub fn convert_device_node_to_text(
+        &self, node: &DevicePath, display_only: bool, allow_shortcuts: bool) -> *mut Char16 {
+
+        (self.convert_device_node_to_text)(node, display_only, allow_shortcuts)
+
+    }
+
+    /// Converts `path` to text, such as `PciRoot(0x0)/Pci(0x1F,0x2)/Sata(0x0,0xFFFF,0x0)`,
+    /// freeing the firmware allocation once decoded.
+    #[cfg(feature = "exts")]
+    pub fn path_to_string(
+        &self,
+        bt: &BootServices,
+        path: &DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> crate::Result<String> {
+
+        let text = self.convert_device_path_to_text(path, display_only, allow_shortcuts);
+        pool_text_to_string(bt, text)
+
+    }
+
+    /// Converts the single node `node` to text, such as `Pci(0x1F,0x2)`,
+    /// freeing the firmware allocation once decoded.
+    #[cfg(feature = "exts")]
+    pub fn node_to_string(
+        &self,
+        bt: &BootServices,
+        node: &DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> crate::Result<String> {
+
+        let text = self.convert_device_node_to_text(node, display_only, allow_shortcuts);
+        pool_text_to_string(bt, text)
+
+    }
+
+}
+
+// Decode a text allocated from pool by `DevicePathToText`, then free it.
+#[cfg(feature = "exts")]
+fn pool_text_to_string(bt: &BootServices, text: *mut Char16) -> crate::Result<String> {
+
+    if text.is_null() {
+        return Err(Status::OUT_OF_RESOURCES.into());
+    }
+
+    let s = unsafe { CStr16::from_ptr(text) }.to_u16_slice();
+    let s: String = core::char::decode_utf16(s.iter().copied())
+        .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+        .collect();
+    bt.free_pool(text as *mut u8)?.log();
+    Ok(s.into())
+
+}
+
+/// Returns the text form of the device path of `handle`, for logging.
+///
+/// The text is produced by the `DevicePathToText` protocol, falling back to
+/// the `Debug` form of the path when the firmware lacks the protocol. Fails
+/// with `UNSUPPORTED` if `handle` has no device path.
+#[cfg(feature = "exts")]
+pub fn device_path_text(bt: &BootServices, handle: Handle) -> crate::Result<String> {
+
+    let path = bt.handle_protocol::<DevicePath>(handle)?.log();
+    let path = unsafe { &*path.get() };
+
+    let to_text = match bt.locate_protocol::<DevicePathToText>() {
+        Ok(to_text) => unsafe { &*to_text.log().get() },
+        Err(_) => return Ok(format!("{:?}", path).into()),
+    };
+
+    to_text.path_to_string(bt, path, true, true)
+
+}
+
+// Payloads ===================================================================
+
+// Common traits all payload types must implement, necessitated by the fact
+// that payloads have a packed representation but not all payloads are
+// necessarily sized at compile time.
+pub trait Payload {
+
+    fn len(&self) -> usize;
+    fn ptr(&self) -> *const u8;
+
+}
+
+impl Payload for () {
+
+    fn len(&self) -> usize { 0 }
+    fn ptr(&self) -> *const u8 { ptr::null() }
+
+}
+
+impl Payload for Guid {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { self as *const _ as *const u8 }
+
+}
+
+/// This Device Path contains ACPI Device IDs that represent a device’s Plug
+/// and Play Hardware ID and its corresponding unique persistent ID. The ACPI
+/// IDs are stored in the ACPI _HID, _CID, and _UID device identification
+/// objects that are associated with a device. The ACPI Device Path contains
+/// values that must match exactly the ACPI name space that is provided by the
+/// platform firmware to the operating system. Refer to the ACPI specification
+/// for a complete description of the.  _HID, _CID, and _UID device
+/// identification objects.
+#[repr(C)]
+pub struct ACPIDevicePath {
+    /// Device’s PnP hardware ID stored in a numeric 32-bit
+    /// compressed EISA-type ID. This value must match the
+    /// corresponding _HID in the ACPI name space.
+    pub hid: u32,
+    /// Unique ID that is required by ACPI if two devices have the
+    /// same _HID. This value must also match the corresponding
+    /// _UID/_HID pair in the ACPI name space. Only the 32-bit
+    /// numeric value type of _UID is supported; thus strings must
+    /// not be used for the _UID in the ACPI name space.
+    pub uid: u32,
+}
+
+impl Payload for ACPIDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { &self.hid as *const _ as *const u8 }
+}
+
+#[repr(C)]
+pub struct MACDevicePath {
+    pub address: MacAddress,
+    pub iftype: HardwareType,
+}
+
+impl Payload for MACDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { &self.address as *const _ as *const u8 }
+}
+
+impl MACDevicePath {
+
+    /// Returns the MAC address carried by this node.
+    pub fn mac(&self) -> MacAddress { self.address }
+
+    /// Create a MAC node from a 6 byte address, zero padding the remainder of
+    /// the 32 byte address field.
+    pub fn from_ethernet(mac: [u8;6], iftype: HardwareType) -> MACDevicePath {
+        MACDevicePath{
+            address: MacAddress::from_bytes(&mac),
+            iftype,
+        }
+    }
+
+    /// Returns the first 6 bytes of the address, the whole address of an
+    /// Ethernet interface.
+    pub fn mac6(&self) -> [u8;6] {
+        let mut mac = [0;6];
+        mac.copy_from_slice(&self.address[..6]);
+        mac
+    }
+
+}
+
+impl From<MacAddress> for MACDevicePath {
+
+    /// Create a MAC node for an Ethernet interface.
+    fn from(address: MacAddress) -> Self {
+        MACDevicePath{
+            address,
+            iftype: HardwareType::Ethernet,
+        }
+    }
+
+}
+
+#[repr(C)]
+pub struct PCIDevicePath {
+    pub function: u8,
+    pub device: u8,
+}
+
+impl Payload for PCIDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { &self.function as *const _ as *const u8 }
+}
+
+/// The Controller device path node, selecting one of the controllers of a
+/// multi-controller device.
+#[repr(C, packed)]
+pub struct ControllerDevicePath {
+    /// Number of the controller.
+    pub controller_number: u32,
+}
+
+impl ControllerDevicePath {
+
+    /// Create a node for the controller numbered `controller_number`.
+    pub fn new(controller_number: u32) -> ControllerDevicePath {
+        ControllerDevicePath{ controller_number }
+    }
+
+}
+
+impl Payload for ControllerDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { self as *const _ as *const u8 }
+
+}
+
+/// The USB device path node, identifying a port of the USB controller or
+/// hub preceding it.
+#[repr(C)]
+pub struct USBDevicePath {
+    /// Port number on the parent hub or controller.
+    pub parent_port: u8,
+    /// USB interface number.
+    pub interface: u8,
+}
+
+impl USBDevicePath {
+
+    /// Create a node for `interface` of the device on `parent_port`.
+    pub fn new(parent_port: u8, interface: u8) -> USBDevicePath {
+        USBDevicePath{ parent_port, interface }
+    }
+
+}
+
+impl Payload for USBDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { self as *const _ as *const u8 }
+}
+
+/// The SATA device path node.
+#[repr(C, packed)]
+pub struct SataDevicePath {
+    /// Port of the HBA the device or port multiplier is attached to.
+    pub hba_port: u16,
+    /// Port of the port multiplier the device is attached to, 0xffff if it
+    /// is attached directly to the HBA.
+    pub port_multiplier_port: u16,
+    /// Logical unit number.
+    pub lun: u16,
+}
+
+impl SataDevicePath {
+
+    /// Create a node for `lun` of the device on `hba_port`, behind
+    /// `port_multiplier_port`.
+    pub fn new(hba_port: u16, port_multiplier_port: u16, lun: u16) -> SataDevicePath {
+        SataDevicePath{ hba_port, port_multiplier_port, lun }
+    }
+
+}
+
+impl Payload for SataDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { self as *const _ as *const u8 }
+
+}
+
+/// The NVM Express namespace device path node.
+#[repr(C, packed)]
+pub struct NvmeDevicePath {
+    /// Namespace identifier, 1 for the first namespace.
+    pub namespace_id: u32,
+    /// IEEE extended unique identifier of the namespace, zero if the device
+    /// reports none.
+    pub namespace_uuid: [u8;8],
+}
+
+impl NvmeDevicePath {
+
+    /// Create a node for the namespace `namespace_id` with the EUI-64
+    /// `namespace_uuid`.
+    pub fn new(namespace_id: u32, namespace_uuid: [u8;8]) -> NvmeDevicePath {
+        NvmeDevicePath{ namespace_id, namespace_uuid }
+    }
+
+}
+
+impl Payload for NvmeDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { self as *const _ as *const u8 }
+
+}
+
+#[repr(C, packed)]
+pub struct IPv4DevicePath {
+    pub local_ip: [u8;4],
+    pub remote_ip: [u8;4],
+    pub local_port: u16,
+    pub remote_port: u16,
+    /// IANA protocol number, see `IPProtocol`.
+    pub protocol: u16,
+    pub static_ip: StaticIPAddr,
+    pub gateway_ip: [u8;4],
+    pub subnet_mask: [u8;4],
+}
+
+impl IPv4DevicePath {
+
+    /// Create a node for a connection to `remote_ip`, with the local address
+    /// assigned by DHCP.
+    pub fn new(remote_ip: Ipv4Addr, remote_port: u16, protocol: IPProtocol) -> IPv4DevicePath {
+        IPv4DevicePath{
+            local_ip: [0;4],
+            remote_ip: remote_ip.into(),
+            local_port: 0,
+            remote_port,
+            protocol: u8::from(protocol) as u16,
+            static_ip: StaticIPAddr::DHCP,
+            gateway_ip: [0;4],
+            subnet_mask: [0;4],
+        }
+    }
+
+    /// Returns the protocol of the connection, if it is a known one.
+    pub fn ip_protocol(&self) -> Option<IPProtocol> {
+        u8::try_from(self.protocol).ok().and_then(|p| IPProtocol::try_from(p).ok())
+    }
+
+}
+
+impl Payload for IPv4DevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { &self.local_ip as *const _ as *const u8 }
+
+}
+
+// The fixed part of an iSCSI device path node, preceding the target name.
+#[repr(C, packed)]
+struct IscsiHeader {
+    protocol: u16,
+    login_options: u16,
+    lun: [u8;8],
+    target_portal_group_tag: u16,
+}
+
+/// The iSCSI device path node. The node ends with the iSCSI target name,
+/// which is not NUL terminated and whose length is given by the length of
+/// the node, so the payload holds the name after the fixed fields.
+#[cfg(feature = "exts")]
+pub struct IscsiDevicePath {
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "exts")]
+impl IscsiDevicePath {
+
+    /// Create a node for a TCP session to the given LUN of the target named
+    /// `target_name`, usually an IQN, with default login options.
+    pub fn new(lun: [u8;8], target_name: &str) -> IscsiDevicePath {
+
+        let header = IscsiHeader{
+            protocol: 0,
+            login_options: 0,
+            lun,
+            target_portal_group_tag: 0,
+        };
+
+        let mut buf = Vec::with_capacity(size_of::<IscsiHeader>() + target_name.len());
+        buf.extend_from_slice(unsafe {
+            core::slice::from_raw_parts(&header as *const _ as *const u8, size_of::<IscsiHeader>())
+        });
+        buf.extend_from_slice(target_name.as_bytes());
+        IscsiDevicePath{ buf }
+
+    }
+
+    fn header(&self) -> &IscsiHeader {
+        unsafe { &*(self.buf.as_ptr() as *const IscsiHeader) }
+    }
+
+    fn header_mut(&mut self) -> &mut IscsiHeader {
+        unsafe { &mut *(self.buf.as_mut_ptr() as *mut IscsiHeader) }
+    }
+
+    /// Network protocol, 0 for TCP.
+    pub fn protocol(&self) -> u16 { self.header().protocol }
+
+    /// iSCSI login options.
+    pub fn login_options(&self) -> u16 { self.header().login_options }
+
+    /// Set the iSCSI login options.
+    pub fn set_login_options(&mut self, login_options: u16) {
+        self.header_mut().login_options = login_options;
+    }
+
+    /// iSCSI logical unit number, in the byte order of the iSCSI header.
+    pub fn lun(&self) -> [u8;8] { self.header().lun }
+
+    /// iSCSI target portal group tag the initiator intends to establish a
+    /// session with.
+    pub fn target_portal_group_tag(&self) -> u16 { self.header().target_portal_group_tag }
+
+    /// Set the iSCSI target portal group tag.
+    pub fn set_target_portal_group_tag(&mut self, tag: u16) {
+        self.header_mut().target_portal_group_tag = tag;
+    }
+
+    /// Returns the name of the target.
+    pub fn target_name(&self) -> &str {
+
+        // the buffer was built from a `&str` following the fixed part
+        let name = &self.buf[size_of::<IscsiHeader>()..];
+        unsafe { core::str::from_utf8_unchecked(name) }
+
+    }
+
+}
+
+#[cfg(feature = "exts")]
+impl Payload for IscsiDevicePath {
+
+    fn len(&self) -> usize { self.buf.len() }
+    fn ptr(&self) -> *const u8 { self.buf.as_ptr() }
+
+}
+
+/// The VLAN device path node, following the MAC node of a tagged network
+/// interface.
+#[repr(C)]
+pub struct VLANDevicePath {
+    /// VLAN identifier, 0 to 4094, in little endian byte order.
+    pub vlan_id: [u8;2],
+}
+
+impl VLANDevicePath {
+
+    /// Create a node for the given VLAN identifier.
+    pub fn new(vlan_id: u16) -> VLANDevicePath {
+        VLANDevicePath{ vlan_id: vlan_id.to_le_bytes() }
+    }
+
+    /// Returns the VLAN identifier.
+    pub fn vlan_id(&self) -> u16 { u16::from_le_bytes(self.vlan_id) }
+
+}
+
+impl Payload for VLANDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { &self.vlan_id as *const _ as *const u8 }
+
+}
+
+#[repr(C)]
+pub struct URIDevicePath {
+    pub uri: &'static str, //TODO probably non static lifetime better
+}
+
+impl URIDevicePath {
+
+    /// Returns the scheme of the URI, the part before `://`, or `None` if
+    /// the URI has no scheme.
+    pub fn scheme(&self) -> Option<&str> {
+
+        let end = self.uri.find("://")?;
+        if end == 0 { None } else { Some(&self.uri[..end]) }
+
+    }
+
+    /// Returns true if the URI has a scheme, and it is one of `allowed`.
+    /// Schemes are compared case insensitively.
+    pub fn is_allowed_scheme(&self, allowed: &[&str]) -> bool {
+
+        match self.scheme() {
+            Some(scheme) => allowed.iter().any(|a| a.eq_ignore_ascii_case(scheme)),
+            None => false,
+        }
+
+    }
+
+}
+
+impl Payload for URIDevicePath {
+
+    fn len(&self) -> usize { self.uri.len() }
+    fn ptr(&self) -> *const u8 { self.uri as *const _ as *const u8 }
+
+}
+
+/// The File Path Media Device Path, holding the NUL terminated UCS-2 path of
+/// a file, e.g. `\EFI\BOOT\BOOTX64.EFI`, relative to the device preceding it.
+#[cfg(feature = "exts")]
+pub struct FileDevicePath {
+    path: Vec<u16>,
+}
+
+#[cfg(feature = "exts")]
+impl FileDevicePath {
+
+    /// Encode `path` as the payload of a file node. Fails if `path` holds
+    /// characters outside of UCS-2.
+    pub fn new(path: &str) -> crate::Result<FileDevicePath> {
+
+        let mut buf = vec![0u16; path.chars().count() + 1];
+        let len = ucs2::encode(path, &mut buf)?;
+        buf.truncate(len + 1);
+        Ok(FileDevicePath{ path: buf }.into())
+
+    }
+
+}
+
+#[cfg(feature = "exts")]
+impl Payload for FileDevicePath {
+
+    fn len(&self) -> usize { self.path.len() * size_of::<u16>() }
+    fn ptr(&self) -> *const u8 { self.path.as_ptr() as *const u8 }
+
+}
+
+/// A vendor defined device path node, of the hardware, messaging or media
+/// type, holding a vendor GUID followed by data whose format the vendor
+/// defines.
+#[cfg(feature = "exts")]
+pub struct VendorDevicePath {
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "exts")]
+impl VendorDevicePath {
+
+    /// Create the payload of a vendor node carrying `data`.
+    pub fn new(guid: Guid, data: &[u8]) -> VendorDevicePath {
+
+        let mut buf = Vec::with_capacity(size_of::<Guid>() + data.len());
+        buf.extend_from_slice(unsafe {
+            core::slice::from_raw_parts(&guid as *const _ as *const u8, size_of::<Guid>())
+        });
+        buf.extend_from_slice(data);
+        VendorDevicePath{ buf }
+
+    }
+
+    /// Returns the vendor GUID.
+    pub fn guid(&self) -> Guid {
+        unsafe { ptr::read_unaligned(self.buf.as_ptr() as *const Guid) }
+    }
+
+    /// Returns the vendor defined data.
+    pub fn data(&self) -> &[u8] {
+        &self.buf[size_of::<Guid>()..]
+    }
+
+}
+
+#[cfg(feature = "exts")]
+impl Payload for VendorDevicePath {
+
+    fn len(&self) -> usize { self.buf.len() }
+    fn ptr(&self) -> *const u8 { self.buf.as_ptr() }
+
+}
+
+/// The Hard Drive Media Device Path is used to represent a partition on a
+/// hard drive.
+#[repr(C, packed)]
+pub struct HardDriveDevicePath {
+    /// Entry in a partition table, starting with entry 1.
+    pub partition_number: u32,
+    /// Starting LBA of the partition on the hard drive.
+    pub partition_start: u64,
+    /// Size of the partition in units of logical blocks.
+    pub partition_size: u64,
+    /// Signature unique to this partition, its format depends on
+    /// `signature_type`.
+    pub partition_signature: [u8;16],
+    /// Partition format, 0x01 for a PC-AT MBR and 0x02 for a GPT.
+    pub partition_format: u8,
+    /// Type of the signature, 0x01 for an MBR signature and 0x02 for a GUID.
+    pub signature_type: u8,
+}
+
+impl HardDriveDevicePath {
+
+    /// Returns the GPT partition GUID if the signature is a GUID.
+    pub fn gpt_guid(&self) -> Option<Guid> {
+
+        if self.signature_type != 0x02 {
+            return None;
+        }
+
+        let signature = self.partition_signature;
+        Some(unsafe { ptr::read_unaligned(signature.as_ptr() as *const Guid) })
+
+    }
+
+}
+
+impl Payload for HardDriveDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { self as *const _ as *const u8 }
+
+}
+
+/// Kind of a RAM disk, given by the disk type GUID of its device path node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RamDiskKind {
+    /// A virtual disk, e.g. a raw disk image.
+    VirtualDisk,
+    /// A virtual CD, e.g. an ISO image.
+    VirtualCd,
+}
+
+/// Disk type GUID of a volatile virtual disk RAM disk.
+pub const VIRTUAL_DISK_GUID: Guid = Guid::from_values(
+    0x77ab535a,
+    0x45fc,
+    0x624b,
+    0x5560,
+    [0xf7, 0xb2, 0x81, 0xd1, 0xf9, 0x6e],
+);
+
+/// Disk type GUID of a volatile virtual CD RAM disk.
+pub const VIRTUAL_CD_GUID: Guid = Guid::from_values(
+    0x3d5abd30,
+    0x4175,
+    0x87ce,
+    0x6d64,
+    [0xd2, 0xad, 0xe5, 0x23, 0xc4, 0xbb],
+);
+
+impl RamDiskKind {
+
+    /// Returns the disk type GUID of this kind.
+    pub fn guid(self) -> Guid {
+        match self {
+            RamDiskKind::VirtualDisk => VIRTUAL_DISK_GUID,
+            RamDiskKind::VirtualCd => VIRTUAL_CD_GUID,
+        }
+    }
+
+    /// Returns the kind of a disk type GUID, or `None` for other types.
+    pub fn from_guid(guid: &Guid) -> Option<RamDiskKind> {
+        match *guid {
+            VIRTUAL_DISK_GUID => Some(RamDiskKind::VirtualDisk),
+            VIRTUAL_CD_GUID => Some(RamDiskKind::VirtualCd),
+            _ => None,
+        }
+    }
+
+}
+
+/// The RAM Disk Media Device Path, describing a disk image held in memory.
+#[repr(C, packed)]
+pub struct RamDiskDevicePath {
+    /// Address of the first byte of the disk.
+    pub starting_address: u64,
+    /// Address of the last byte of the disk.
+    pub ending_address: u64,
+    /// Disk type GUID, see `RamDiskKind`.
+    pub disk_type: Guid,
+    /// Instance number of the disk, distinguishing disks of the same type at
+    /// the same addresses.
+    pub instance: u16,
+}
+
+impl RamDiskDevicePath {
+
+    /// Create a node for the first instance of a disk of `kind` spanning
+    /// `start` to `end` inclusive.
+    pub fn new(start: u64, end: u64, kind: RamDiskKind) -> RamDiskDevicePath {
+        RamDiskDevicePath{
+            starting_address: start,
+            ending_address: end,
+            disk_type: kind.guid(),
+            instance: 0,
+        }
+    }
+
+    /// Returns the kind of the disk, or `None` for an unknown disk type.
+    pub fn kind(&self) -> Option<RamDiskKind> {
+        let disk_type = self.disk_type;
+        RamDiskKind::from_guid(&disk_type)
+    }
+
+}
+
+impl Payload for RamDiskDevicePath {
+
+    fn len(&self) -> usize { size_of::<Self>() }
+    fn ptr(&self) -> *const u8 { self as *const _ as *const u8 }
+
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+pub enum  StaticIPAddr {
+    DHCP = 0,
+    Static = 1,
 }
 
 #[repr(u8)]
@@ -735,6 +2298,170 @@ pub enum IPProtocol {
     Reserved            = 255,
 }
 
+impl IPProtocol {
+
+    /// Returns the IANA keyword of the protocol, e.g. `"TCP"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            IPProtocol::HOPOPT               => "HOPOPT",
+            IPProtocol::ICMP                 => "ICMP",
+            IPProtocol::IGMP                 => "IGMP",
+            IPProtocol::GGP                  => "GGP",
+            IPProtocol::IPv4                 => "IPv4",
+            IPProtocol::ST                   => "ST",
+            IPProtocol::TCP                  => "TCP",
+            IPProtocol::CBT                  => "CBT",
+            IPProtocol::EGP                  => "EGP",
+            IPProtocol::IGP                  => "IGP",
+            IPProtocol::BBN_RCC_MON          => "BBN-RCC-MON",
+            IPProtocol::NVP_II               => "NVP-II",
+            IPProtocol::PUP                  => "PUP",
+            IPProtocol::ARGUS                => "ARGUS",
+            IPProtocol::EMCON                => "EMCON",
+            IPProtocol::XNET                 => "XNET",
+            IPProtocol::CHAOS                => "CHAOS",
+            IPProtocol::UDP                  => "UDP",
+            IPProtocol::MUX                  => "MUX",
+            IPProtocol::DCN_MEAS             => "DCN-MEAS",
+            IPProtocol::HMP                  => "HMP",
+            IPProtocol::PRM                  => "PRM",
+            IPProtocol::XNS_IDP              => "XNS-IDP",
+            IPProtocol::TRUNK_1              => "TRUNK-1",
+            IPProtocol::TRUNK_2              => "TRUNK-2",
+            IPProtocol::LEAF_1               => "LEAF-1",
+            IPProtocol::LEAF_2               => "LEAF-2",
+            IPProtocol::RDP                  => "RDP",
+            IPProtocol::IRTP                 => "IRTP",
+            IPProtocol::ISO_TP4              => "ISO-TP4",
+            IPProtocol::NETBLT               => "NETBLT",
+            IPProtocol::MFE_NSP              => "MFE-NSP",
+            IPProtocol::MERIT_INP            => "MERIT-INP",
+            IPProtocol::DCCP                 => "DCCP",
+            IPProtocol::_3PC                 => "3PC",
+            IPProtocol::IDPR                 => "IDPR",
+            IPProtocol::XTP                  => "XTP",
+            IPProtocol::DDP                  => "DDP",
+            IPProtocol::IDPR_CMTP            => "IDPR-CMTP",
+            IPProtocol::TPPP                 => "TPPP",
+            IPProtocol::IL                   => "IL",
+            IPProtocol::IPv6                 => "IPv6",
+            IPProtocol::SDRP                 => "SDRP",
+            IPProtocol::IPv6_Route           => "IPv6-Route",
+            IPProtocol::IPv6_Frag            => "IPv6-Frag",
+            IPProtocol::IDRP                 => "IDRP",
+            IPProtocol::RSVP                 => "RSVP",
+            IPProtocol::GRE                  => "GRE",
+            IPProtocol::DSR                  => "DSR",
+            IPProtocol::BNA                  => "BNA",
+            IPProtocol::ESP                  => "ESP",
+            IPProtocol::AH                   => "AH",
+            IPProtocol::I_NLSP               => "I-NLSP",
+            IPProtocol::SWIPE                => "SWIPE",
+            IPProtocol::NARP                 => "NARP",
+            IPProtocol::MOBILE               => "MOBILE",
+            IPProtocol::TLSP                 => "TLSP",
+            IPProtocol::SKIP                 => "SKIP",
+            IPProtocol::IPv6_ICMP            => "IPv6-ICMP",
+            IPProtocol::IPv6_NoNxt           => "IPv6-NoNxt",
+            IPProtocol::IPv6_Opts            => "IPv6-Opts",
+            IPProtocol::ANYHOST              => "ANYHOST",
+            IPProtocol::CFTP                 => "CFTP",
+            IPProtocol::ANYLOCAL             => "ANYLOCAL",
+            IPProtocol::SAT_EXPAK            => "SAT-EXPAK",
+            IPProtocol::KRYPTOLAN            => "KRYPTOLAN",
+            IPProtocol::RVD                  => "RVD",
+            IPProtocol::IPPC                 => "IPPC",
+            IPProtocol::ANYDFS               => "ANYDFS",
+            IPProtocol::SAT_MON              => "SAT-MON",
+            IPProtocol::VISA                 => "VISA",
+            IPProtocol::IPCV                 => "IPCV",
+            IPProtocol::CPNX                 => "CPNX",
+            IPProtocol::CPHB                 => "CPHB",
+            IPProtocol::WSN                  => "WSN",
+            IPProtocol::PVP                  => "PVP",
+            IPProtocol::BR_SAT_MON           => "BR-SAT-MON",
+            IPProtocol::SUN_ND               => "SUN-ND",
+            IPProtocol::WB_MON               => "WB-MON",
+            IPProtocol::WB_EXPAK             => "WB-EXPAK",
+            IPProtocol::ISO_IP               => "ISO-IP",
+            IPProtocol::VMTP                 => "VMTP",
+            IPProtocol::SECURE_VMTP          => "SECURE-VMTP",
+            IPProtocol::VINES                => "VINES",
+            IPProtocol::TTP_IPTM             => "TTP-IPTM",
+            IPProtocol::NSFNET_IGP           => "NSFNET-IGP",
+            IPProtocol::DGP                  => "DGP",
+            IPProtocol::TCF                  => "TCF",
+            IPProtocol::EIGRP                => "EIGRP",
+            IPProtocol::OSPFIGP              => "OSPFIGP",
+            IPProtocol::Sprite_RPC           => "Sprite-RPC",
+            IPProtocol::LARP                 => "LARP",
+            IPProtocol::MTP                  => "MTP",
+            IPProtocol::AX25                 => "AX25",
+            IPProtocol::IPIP                 => "IPIP",
+            IPProtocol::MICP                 => "MICP",
+            IPProtocol::SCC_SP               => "SCC-SP",
+            IPProtocol::ETHERIP              => "ETHERIP",
+            IPProtocol::ENCAP                => "ENCAP",
+            IPProtocol::ANYPE                => "ANYPE",
+            IPProtocol::GMTP                 => "GMTP",
+            IPProtocol::IFMP                 => "IFMP",
+            IPProtocol::PNNI                 => "PNNI",
+            IPProtocol::PIM                  => "PIM",
+            IPProtocol::ARIS                 => "ARIS",
+            IPProtocol::SCPS                 => "SCPS",
+            IPProtocol::QNX                  => "QNX",
+            IPProtocol::A_N                  => "A/N",
+            IPProtocol::IPComp               => "IPComp",
+            IPProtocol::SNP                  => "SNP",
+            IPProtocol::Compaq_Peer          => "Compaq-Peer",
+            IPProtocol::IPX_in_IP            => "IPX-in-IP",
+            IPProtocol::VRRP                 => "VRRP",
+            IPProtocol::PGM                  => "PGM",
+            IPProtocol::ANYZH                => "ANYZH",
+            IPProtocol::L2TP                 => "L2TP",
+            IPProtocol::DDX                  => "DDX",
+            IPProtocol::IATP                 => "IATP",
+            IPProtocol::STP                  => "STP",
+            IPProtocol::SRP                  => "SRP",
+            IPProtocol::UTI                  => "UTI",
+            IPProtocol::SMP                  => "SMP",
+            IPProtocol::SM                   => "SM",
+            IPProtocol::PTP                  => "PTP",
+            IPProtocol::ISIS_over_IPv4       => "ISIS over IPv4",
+            IPProtocol::FIRE                 => "FIRE",
+            IPProtocol::CRTP                 => "CRTP",
+            IPProtocol::CRUDP                => "CRUDP",
+            IPProtocol::SSCOPMCE             => "SSCOPMCE",
+            IPProtocol::IPLT                 => "IPLT",
+            IPProtocol::SPS                  => "SPS",
+            IPProtocol::PIPE                 => "PIPE",
+            IPProtocol::SCTP                 => "SCTP",
+            IPProtocol::FC                   => "FC",
+            IPProtocol::RSVP_E2E_IGNORE      => "RSVP-E2E-IGNORE",
+            IPProtocol::Mobility_Header      => "Mobility Header",
+            IPProtocol::UDPLite              => "UDPLite",
+            IPProtocol::MPLS_in_IP           => "MPLS-in-IP",
+            IPProtocol::manet                => "manet",
+            IPProtocol::HIP                  => "HIP",
+            IPProtocol::Shim6                => "Shim6",
+            IPProtocol::WESP                 => "WESP",
+            IPProtocol::ROHC                 => "ROHC",
+            IPProtocol::Ethernet             => "Ethernet",
+            IPProtocol::EXP0                 => "EXP0",
+            IPProtocol::EXP2                 => "EXP2",
+            IPProtocol::Reserved             => "Reserved",
+        }
+    }
+
+    /// Looks up a protocol by its IANA keyword, ignoring ASCII case.
+    pub fn from_name(name: &str) -> Option<IPProtocol> {
+        (0..=u8::MAX)
+            .filter_map(|n| IPProtocol::try_from(n).ok())
+            .find(|p| p.name().eq_ignore_ascii_case(name))
+    }
+
+}
+
 // Debug ======================================================================
 
 impl core::fmt::Debug for DevicePath {
@@ -754,6 +2481,16 @@ impl core::fmt::Debug for DevicePath {
                                 let pl = self.payload::<PCIDevicePath>();
                                 d.field("data", &pl);
                             }
+                            HardwarePathSubType::Controller => {
+                                let pl = self.payload::<ControllerDevicePath>();
+                                d.field("data", &pl);
+                            }
+                            HardwarePathSubType::Vendor => {
+                                if let Some((guid, data)) = self.vendor_data() {
+                                    d.field("guid", &format_args!("{}", guid));
+                                    d.field("data", &data);
+                                }
+                            }
                             _ => {}
                         }
                     }
@@ -790,11 +2527,26 @@ impl core::fmt::Debug for DevicePath {
                                 let pl = self.payload::<IPv4DevicePath>();
                                 d.field("data", &pl);
                             }
-                            MessagingPathSubType::URI => {
-                                /*
-                                let pl = self.payload::<URIDevicePath>();
+                            MessagingPathSubType::USB => {
+                                let pl = self.payload::<USBDevicePath>();
+                                d.field("data", &pl);
+                            }
+                            MessagingPathSubType::Vendor => {
+                                if let Some((guid, data)) = self.vendor_data() {
+                                    d.field("guid", &format_args!("{}", guid));
+                                    d.field("data", &data);
+                                }
+                            }
+                            MessagingPathSubType::NVME => {
+                                let pl = self.payload::<NvmeDevicePath>();
+                                d.field("data", &pl);
+                            }
+                            MessagingPathSubType::SATA => {
+                                let pl = self.payload::<SataDevicePath>();
                                 d.field("data", &pl);
-                                */
+                            }
+                            MessagingPathSubType::URI => {
+                                d.field("uri", &LossyUtf8(self.payload_bytes()));
                             }
                             _ => {}
                         }
@@ -862,17 +2614,8 @@ impl core::fmt::Debug for MACDevicePath {
 
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 
-        let mac = format!("{:x}:{:x}:{:x}:{:x}:{:x}:{:x}", 
-            &self.address[0],
-            &self.address[1],
-            &self.address[2],
-            &self.address[3],
-            &self.address[4],
-            &self.address[5],
-        );
-
         f.debug_struct("MACDevicePath")
-            .field("address", &mac)
+            .field("address", &self.address)
             .field("iftype", &self.iftype)
             .finish()
 
@@ -887,9 +2630,9 @@ impl core::fmt::Debug for IPv4DevicePath {
         f.debug_struct("IPv4DevicePath")
             .field("local_ip", &self.local_ip)
             .field("remote_ip", &self.remote_ip)
-            .field("local_port", &self.local_port)
-            .field("remote_port", &self.remote_port)
-            .field("protocol", &self.protocol)
+            .field("local_port", &{ self.local_port })
+            .field("remote_port", &{ self.remote_port })
+            .field("protocol", &self.ip_protocol())
             .field("static_ip", &self.static_ip)
             .field("gateway_ip", &self.gateway_ip)
             .field("subnet_mask", &self.subnet_mask)
@@ -911,7 +2654,67 @@ impl core::fmt::Debug for PCIDevicePath {
     }
 }
 
-/*
+impl core::fmt::Debug for ControllerDevicePath {
+
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+
+        f.debug_struct("ControllerDevicePath")
+            .field("controller_number", &{ self.controller_number })
+            .finish()
+
+    }
+}
+
+impl core::fmt::Debug for SataDevicePath {
+
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+
+        f.debug_struct("SataDevicePath")
+            .field("hba_port", &{ self.hba_port })
+            .field("port_multiplier_port", &{ self.port_multiplier_port })
+            .field("lun", &{ self.lun })
+            .finish()
+
+    }
+}
+
+impl core::fmt::Debug for NvmeDevicePath {
+
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+
+        f.debug_struct("NvmeDevicePath")
+            .field("namespace_id", &format_args!("{:#x}", { self.namespace_id }))
+            .field("namespace_uuid", &self.namespace_uuid)
+            .finish()
+
+    }
+}
+
+#[cfg(feature = "exts")]
+impl core::fmt::Debug for VendorDevicePath {
+
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+
+        f.debug_struct("VendorDevicePath")
+            .field("guid", &format_args!("{}", self.guid()))
+            .field("data", &self.data())
+            .finish()
+
+    }
+}
+
+impl core::fmt::Debug for USBDevicePath {
+
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+
+        f.debug_struct("USBDevicePath")
+            .field("parent_port", &self.parent_port)
+            .field("interface", &self.interface)
+            .finish()
+
+    }
+}
+
 impl core::fmt::Debug for URIDevicePath {
 
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -923,4 +2726,40 @@ impl core::fmt::Debug for URIDevicePath {
     }
 
 }
-*/
+
+// Renders bytes as a quoted string, replacing invalid UTF-8 sequences with
+// U+FFFD. URI nodes carry their URI unterminated and without any guarantee of
+// being valid UTF-8.
+struct LossyUtf8<'a>(&'a [u8]);
+
+impl core::fmt::Debug for LossyUtf8<'_> {
+
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+
+        use core::fmt::Write;
+
+        f.write_char('"')?;
+        let mut bytes = self.0;
+        loop {
+            match core::str::from_utf8(bytes) {
+                Ok(s) => {
+                    write!(f, "{}", s.escape_debug())?;
+                    break;
+                }
+                Err(e) => {
+                    let (valid, rest) = bytes.split_at(e.valid_up_to());
+                    let s = unsafe { core::str::from_utf8_unchecked(valid) };
+                    write!(f, "{}", s.escape_debug())?;
+                    f.write_char(core::char::REPLACEMENT_CHARACTER)?;
+                    match e.error_len() {
+                        Some(len) => bytes = &rest[len..],
+                        None => break,
+                    }
+                }
+            }
+        }
+        f.write_char('"')
+
+    }
+
+}
@@ -22,7 +22,7 @@ use crate::{
 use core::{ffi::c_void, str};
 
 #[cfg(feature = "exts")]
-use alloc_api::boxed::Box;
+use alloc_api::{boxed::Box, string::String, vec};
 
 /// The Loaded Image protocol. This can be opened on any image handle using the `HandleProtocol` boot service.
 #[repr(C)]
@@ -35,7 +35,7 @@ pub struct LoadedImage {
 
     // Source location of the image
     device_handle: Handle,
-    _file_path: *const c_void, // TODO: not supported yet
+    file_path: *const DevicePath,
     _reserved: *const c_void,
 
     // Image load options
@@ -48,10 +48,13 @@ pub struct LoadedImage {
     image_code_type: MemoryType,
     image_data_type: MemoryType,
     /// This is a callback that a loaded image can use to do cleanup. It is called by the
-    /// UnloadImage boot service.
-    unload: extern "efiapi" fn(image_handle: Handle) -> Status,
+    /// UnloadImage boot service. Images that cannot be unloaded leave it null.
+    unload: Option<extern "efiapi" fn(image_handle: Handle) -> Status>,
 }
 
+/// Revision of the loaded image protocol defined by the specification.
+pub const LOADED_IMAGE_PROTOCOL_REVISION: u32 = 0x1000;
+
 /// Errors that can be raised during parsing of the load options.
 #[derive(Debug)]
 pub enum LoadOptionsError {
@@ -62,11 +65,72 @@ pub enum LoadOptionsError {
 }
 
 impl LoadedImage {
+    /// Returns the raw revision of the protocol, see `decode_revision`.
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    /// Decode a revision into its major and minor parts, packed in the upper
+    /// and lower 16 bits.
+    ///
+    /// The specification defines `EFI_LOADED_IMAGE_PROTOCOL_REVISION` as
+    /// 0x1000 rather than a packed value, which current firmware reports. It
+    /// is the first revision of the protocol and decodes as 1.0.
+    pub fn decode_revision(revision: u32) -> (u16, u16) {
+        match revision {
+            LOADED_IMAGE_PROTOCOL_REVISION => (1, 0),
+            _ => ((revision >> 16) as u16, revision as u16),
+        }
+    }
+
+    /// Returns true if the revision of the protocol is at least
+    /// `major.minor`, see `decode_revision`.
+    pub fn is_revision_at_least(&self, major: u16, minor: u16) -> bool {
+        Self::decode_revision(self.revision) >= (major, minor)
+    }
+
+    /// Returns the handle of the image that loaded this image, or of the
+    /// firmware for images loaded by the firmware itself.
+    pub fn parent(&self) -> Handle {
+        self.parent_handle
+    }
+
+    /// Returns a pointer to the system table the image was started with.
+    ///
+    /// This is usually the system table passed to the entry point, but it
+    /// may differ from it when the image was started by a nested
+    /// environment providing its own system table.
+    pub fn system_table(&self) -> *const c_void {
+        self.system_table
+    }
+
+    /// Invoke the unload callback of the image, releasing the resources it
+    /// holds. Returns `UNSUPPORTED` if the image does not support unloading.
+    ///
+    /// # Safety
+    ///
+    /// `image_handle` must be the handle this protocol was opened on. The
+    /// callback trusts the handle it is passed, so passing the handle of any
+    /// other image is undefined behavior. The image must not be used after it
+    /// has been unloaded.
+    pub unsafe fn unload(&self, image_handle: Handle) -> crate::Result<()> {
+        match self.unload {
+            Some(unload) => unload(image_handle).into(),
+            None => Err(Status::UNSUPPORTED.into()),
+        }
+    }
+
     /// Returns a handle to the storage device on which the image is located.
     pub fn device(&self) -> Handle {
         self.device_handle
     }
 
+    /// Returns the path of the image file, relative to the device it was
+    /// loaded from, or `None` if the image was not loaded from a file.
+    pub fn file_path(&self) -> Option<&DevicePath> {
+        unsafe { self.file_path.as_ref() }
+    }
+
     /// Get the load options of the given image. If the image was executed from the EFI shell, or from a boot
     /// option, this is the command line that was used to execute it as a string. If no options were given, this
     /// returns `Ok("")`.
@@ -81,17 +145,59 @@ impl LoadedImage {
         }
     }
 
+    /// Returns the size in bytes of the load options, including their NUL
+    /// terminator when they are a string.
+    pub fn load_options_size(&self) -> u32 {
+        self.load_options_size
+    }
+
+    /// Get the load options of the given image as an owned string, see
+    /// `load_options`.
+    ///
+    /// Exactly `load_options_size` bytes are read, up to the first NUL, so
+    /// options lacking a terminator are decoded safely.
+    #[cfg(feature = "exts")]
+    pub fn load_options_string(&self) -> Result<String, LoadOptionsError> {
+
+        if self.load_options.is_null() {
+            return Ok(String::new());
+        }
+
+        // the options need not be NUL terminated, only their size bounds them
+        let units = self.load_options_size as usize / core::mem::size_of::<u16>();
+        let ucs2_slice = unsafe {
+            core::slice::from_raw_parts(self.load_options as *const u16, units)
+        };
+        let ucs2_slice = match ucs2_slice.iter().position(|&c| c == 0) {
+            Some(nul) => &ucs2_slice[..nul],
+            None => ucs2_slice,
+        };
+
+        // each UCS-2 code unit decodes to at most 3 bytes of UTF-8
+        let mut buffer = vec![0u8; ucs2_slice.len() * 3];
+        let length =
+            ucs2::decode(ucs2_slice, &mut buffer).map_err(|_| LoadOptionsError::BufferTooSmall)?;
+        buffer.truncate(length);
+        String::from_utf8(buffer).map_err(|_| LoadOptionsError::NotValidUtf8)
+
+    }
+
     /// Set the load options of the given image.
+    ///
+    /// The options are stored NUL terminated, and their size is given in
+    /// bytes including the terminator.
     #[cfg(feature = "exts")]
     pub fn set_load_options(&mut self, s: &str) -> Result<(), LoadOptionsError> {
 
-        let mut buf = unsafe{ Box::<[u16]>::new_uninit_slice(s.len()).assume_init() };
+        // a UTF-8 string never has fewer bytes than UCS-2 code units, the
+        // extra unit is the NUL terminator
+        let mut buf = vec![0u16; s.len() + 1].into_boxed_slice();
 
         let len = ucs2::encode(s, &mut *buf).map_err(|_| LoadOptionsError::BufferTooSmall)?;
 
         let opts = Box::into_raw(buf) as *const Char16;
         self.load_options = opts;
-        self.load_options_size = len as u32;
+        self.load_options_size = ((len + 1) * core::mem::size_of::<u16>()) as u32;
 
         Ok(())
 
@@ -101,4 +207,14 @@ impl LoadedImage {
     pub fn info(&self) -> (usize, u64) {
         (self.image_base, self.image_size)
     }
+
+    /// Returns the memory type the code sections of the image were loaded as.
+    pub fn code_type(&self) -> MemoryType {
+        self.image_code_type
+    }
+
+    /// Returns the memory type the data sections of the image were loaded as.
+    pub fn data_type(&self) -> MemoryType {
+        self.image_data_type
+    }
 }
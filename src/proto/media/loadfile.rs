@@ -3,6 +3,23 @@
 use crate::proto::Protocol;
 use crate::{unsafe_guid, Result, Status};
 use core::ffi::c_void;
+use core::ptr;
+#[cfg(feature = "exts")]
+use crate::{
+    Handle,
+    Identify,
+    table::boot::BootServices,
+    proto::loaded_image::{
+        self,
+        DevicePathBox,
+        DevicePathPayload,
+        DeviceType,
+        MediaPathSubType,
+        LINUX_INITRD_MEDIA_GUID,
+    },
+};
+#[cfg(feature = "exts")]
+use alloc_api::{boxed::Box, vec, vec::Vec};
 //use std::os::raw::c_char;
 
 /// Allows loading of files from a number of provider drivers.
@@ -31,6 +48,189 @@ impl<T> LoadFile<T> {
 
     }
 
+    /// Load a file into a newly allocated buffer.
+    ///
+    /// The provider is first called with a null buffer to learn the size of
+    /// the file, then again with a buffer of that size.
+    #[cfg(feature = "exts")]
+    pub fn load(&mut self, root: &mut T, boot_policy: bool) -> Result<Vec<u8>> {
+
+        let mut size = 0u64;
+        let status =
+            (self.load_file)(self, root, boot_policy, &mut size, ptr::null_mut());
+        if status != Status::BUFFER_TOO_SMALL && status.is_error() {
+            return Err(status.into());
+        }
+
+        // A provider without data reports success with a zero size
+        if size == 0 {
+            return status.into_with_val(Vec::new);
+        }
+
+        let mut buffer = vec![0u8; size as usize];
+        let status = (self.load_file)(
+            self, root, boot_policy, &mut size, buffer.as_mut_ptr() as *mut c_void);
+        if status.is_error() {
+            return Err(status.into());
+        }
+
+        buffer.truncate(size as usize);
+        status.into_with_val(|| buffer)
+
+    }
+
+    /// Returns true if the provider has data for `root`, without loading it.
+    ///
+    /// A provider reporting `NOT_FOUND`, or a size of zero, has no data.
+    pub fn is_present(&mut self, root: &mut T, boot_policy: bool) -> Result<bool> {
+
+        let mut size = 0u64;
+        match (self.load_file)(self, root, boot_policy, &mut size, ptr::null_mut()) {
+            Status::NOT_FOUND => Ok(false.into()),
+            Status::BUFFER_TOO_SMALL => Ok((size != 0).into()),
+            status => status.into_with_val(|| size != 0),
+        }
+
+    }
+
+    /// Load a boot option selected by the boot manager, with `boot_policy`
+    /// set. The provider may then resolve `root` loosely, e.g. by starting a
+    /// network boot for a partial device path.
+    #[cfg(feature = "exts")]
+    pub fn load_for_boot(&mut self, root: &mut T) -> Result<Vec<u8>> {
+        self.load(root, true)
+    }
+
+    /// Load exactly the file `root` refers to, with `boot_policy` clear.
+    ///
+    /// This is the only mode `LoadFile2` providers support, including the one
+    /// serving a Linux initrd.
+    #[cfg(feature = "exts")]
+    pub fn load_direct(&mut self, root: &mut T) -> Result<Vec<u8>> {
+        self.load(root, false)
+    }
+
+}
+
+/// A `LoadFile2` provider serving a Linux initrd from memory.
+///
+/// The Linux EFI stub locates the initrd by looking for a `LoadFile2`
+/// instance on a handle bearing the Linux initrd vendor media device path.
+/// The stub first calls `load_file` with a null buffer to learn the size of
+/// the initrd, then calls it again with a buffer of that size.
+#[cfg(feature = "exts")]
+#[repr(C)]
+pub struct InitrdLoadFile {
+    load_file:
+        extern "efiapi" fn(
+            this: &mut InitrdLoadFile,
+            file_path: *const loaded_image::DevicePath,
+            boot_policy: bool,
+            buffer_size: &mut usize,
+            buffer: *mut c_void,
+        ) -> Status,
+    data: &'static [u8],
+    path: DevicePathBox,
+}
+
+#[cfg(feature = "exts")]
+impl InitrdLoadFile {
+
+    /// Install a provider serving `data` on a new handle, along with the
+    /// Linux initrd media device path.
+    ///
+    /// The provider stays installed, and its memory allocated, for the
+    /// remainder of boot services. If either protocol fails to install,
+    /// neither is left on the handle.
+    pub fn install(bt: &BootServices, data: &'static [u8]) -> Result<Handle> {
+
+        let path = loaded_image::DevicePath::new1(DevicePathPayload::create(
+            DeviceType::Media,
+            MediaPathSubType::Vendor as u8,
+            LINUX_INITRD_MEDIA_GUID,
+        ));
+
+        let provider = Box::leak(Box::new(InitrdLoadFile{
+            load_file: Self::serve,
+            data,
+            path,
+        }));
+
+        unsafe {
+            let path = provider.path.as_ptr() as *mut c_void;
+            let handle = match bt.install_protocol_interface(
+                None,
+                &loaded_image::DevicePath::GUID,
+                path,
+            ) {
+                Ok(handle) => handle.log(),
+                Err(err) => {
+                    drop(Box::from_raw(provider));
+                    return Err(err);
+                }
+            };
+
+            let installed = bt.install_protocol_interface(
+                Some(handle),
+                &LoadFile::<loaded_image::DevicePath>::GUID,
+                provider as *mut _ as *mut c_void,
+            );
+
+            // Do not leave a device path without its LoadFile2 behind, or the
+            // loader would find the initrd but fail to read it. The provider
+            // can only be freed once nothing refers to its path anymore.
+            if installed.is_err() {
+                let guid = &loaded_image::DevicePath::GUID;
+                if bt.uninstall_protocol_interface(handle, guid, path).is_ok() {
+                    drop(Box::from_raw(provider));
+                }
+            }
+            installed
+        }
+
+    }
+
+    extern "efiapi" fn serve(
+        this: &mut InitrdLoadFile,
+        file_path: *const loaded_image::DevicePath,
+        boot_policy: bool,
+        buffer_size: &mut usize,
+        buffer: *mut c_void,
+    ) -> Status {
+
+        // LoadFile2 is never used to load a boot option
+        if boot_policy {
+            return Status::UNSUPPORTED;
+        }
+
+        // The initrd is the only file served, so the remaining path must be
+        // empty
+        if file_path.is_null() {
+            return Status::INVALID_PARAMETER;
+        }
+        if unsafe { (*file_path).device_type } != DeviceType::End {
+            return Status::NOT_FOUND;
+        }
+
+        let len = this.data.len();
+        if len == 0 {
+            *buffer_size = 0;
+            return Status::SUCCESS;
+        }
+        if buffer.is_null() || *buffer_size < len {
+            *buffer_size = len;
+            return Status::BUFFER_TOO_SMALL;
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(this.data.as_ptr(), buffer as *mut u8, len);
+        }
+        *buffer_size = len;
+
+        Status::SUCCESS
+
+    }
+
 }
 
 /// The devices specific path of a file. Can take a number of forms as
@@ -33,3 +33,5 @@ pub mod media;
 pub mod pi;
 pub mod dhcp4;
 pub mod ip4_config2;
+pub mod mtftp4;
+pub mod simple_network;
@@ -0,0 +1,256 @@
+//! MTFTP4 protocol
+
+use crate::proto::Protocol;
+use crate::proto::dhcp4::{IPv4Address, Ipv4Addr};
+use crate::{unsafe_guid, Result, Status};
+use core::ptr;
+use core::str;
+#[cfg(feature = "exts")]
+use crate::table::boot::BootServices;
+#[cfg(feature = "exts")]
+use alloc_api::vec::Vec;
+
+/// Well known TFTP server port.
+pub const TFTP_PORT: u16 = 69;
+
+/// TFTP opcode of a data packet.
+pub const OPCODE_DATA: u16 = 3;
+
+/// TFTP opcode of an option acknowledgement packet, see RFC 2347.
+pub const OPCODE_OACK: u16 = 6;
+
+/// The MTFTP4 protocol provides basic services for client-side unicast and
+/// multicast TFTP operations.
+#[repr(C)]
+#[unsafe_guid("78247c57-63db-4708-99c2-a8b4a9a61f6b")]
+#[derive(Protocol)]
+pub struct Mtftp4 {
+    get_mode_data: usize,
+
+    configure: extern "efiapi" fn(
+        this: &mut Mtftp4,
+        mtftp_config_data: *const ConfigData,
+    ) -> Status,
+
+    get_info: extern "efiapi" fn(
+        this: &mut Mtftp4,
+        override_data: *const OverrideData,
+        filename: *const u8,
+        mode_str: *const u8,
+        option_count: u8,
+        option_list: *const Mtftp4Option,
+        packet_length: &mut u32,
+        packet: &mut *mut u8,
+    ) -> Status,
+
+    parse_options: usize,
+    read_file: usize,
+    write_file: usize,
+    read_directory: usize,
+    poll: usize,
+}
+
+impl Mtftp4 {
+
+    /// Initialize, change or reset the default operational setting of the
+    /// instance. A null configuration resets the instance.
+    pub fn configure(&mut self, mtftp_config_data: *const ConfigData) -> Result<()> {
+
+        (self.configure)(self, mtftp_config_data).into()
+
+    }
+
+    /// Returns the size of `filename` in bytes, as reported by the server
+    /// through the transfer size option of RFC 2349.
+    ///
+    /// Fails with `UNSUPPORTED` if the server does not negotiate options,
+    /// answering with data, or acknowledges them without the transfer size.
+    /// Errors sent by the server, e.g. for a missing file, are reported as
+    /// `TFTP_ERROR`.
+    #[cfg(feature = "exts")]
+    pub fn get_file_size(&mut self, filename: &str, bt: &BootServices) -> Result<u64> {
+
+        // the protocol takes NUL terminated strings
+        let mut name = Vec::with_capacity(filename.len() + 1);
+        name.extend_from_slice(filename.as_bytes());
+        name.push(0);
+
+        let option = Mtftp4Option{
+            option_str: b"tsize\0".as_ptr(),
+            value_str: b"0\0".as_ptr(),
+        };
+
+        let mut length = 0u32;
+        let mut packet = ptr::null_mut();
+        let status = (self.get_info)(
+            self,
+            ptr::null(),
+            name.as_ptr(),
+            ptr::null(),
+            1,
+            &option,
+            &mut length,
+            &mut packet,
+        );
+
+        // the packet is allocated from pool by the driver, and freed by the
+        // caller whatever the status
+        let (opcode, size) = if packet.is_null() {
+            (None, None)
+        } else {
+            let bytes = unsafe { core::slice::from_raw_parts(packet, length as usize) };
+            let opcode = match bytes {
+                [a, b, ..] => Some(u16::from_be_bytes([*a, *b])),
+                _ => None,
+            };
+            let size = OackOptions::parse(bytes).and_then(|o| o.tsize());
+            bt.free_pool(packet)?.log();
+            (opcode, size)
+        };
+
+        match status {
+            Status::SUCCESS => {}
+            // a server ignoring options answers with data rather than an OACK
+            Status::PROTOCOL_ERROR if opcode == Some(OPCODE_DATA) => {
+                return Err(Status::UNSUPPORTED.into());
+            }
+            status => return Err(status.into()),
+        }
+
+        match size {
+            Some(size) => Ok(size.into()),
+            None => Err(Status::UNSUPPORTED.into()),
+        }
+
+    }
+
+}
+
+/// EFI_MTFTP4_CONFIG_DATA
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigData {
+    /// Use the default station address and subnet mask of the IP4 driver,
+    /// rather than `station_ip` and `subnet_mask`.
+    pub use_default_setting: bool,
+    /// Station address of the client.
+    pub station_ip: IPv4Address,
+    /// Subnet mask of the client.
+    pub subnet_mask: IPv4Address,
+    /// Local port, zero for any.
+    pub local_port: u16,
+    /// Gateway to reach the server through, zero for none.
+    pub gateway_ip: IPv4Address,
+    /// Address of the TFTP server.
+    pub server_ip: IPv4Address,
+    /// Port of the server requests are initially sent to.
+    pub initial_server_port: u16,
+    /// Number of attempts of each request.
+    pub try_count: u16,
+    /// Timeout of each attempt in seconds.
+    pub timeout_value: u16,
+}
+
+impl ConfigData {
+
+    /// Create a configuration reaching the TFTP server `server` on its well
+    /// known port, using the default station address of the IP4 driver.
+    pub fn new(server: Ipv4Addr) -> ConfigData {
+        ConfigData{
+            use_default_setting: true,
+            station_ip: [0;4],
+            subnet_mask: [0;4],
+            local_port: 0,
+            gateway_ip: [0;4],
+            server_ip: server.into(),
+            initial_server_port: TFTP_PORT,
+            try_count: 4,
+            timeout_value: 3,
+        }
+    }
+
+}
+
+/// EFI_MTFTP4_OVERRIDE_DATA
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct OverrideData {
+    /// Gateway to reach the server through.
+    pub gateway_ip: IPv4Address,
+    /// Address of the TFTP server.
+    pub server_ip: IPv4Address,
+    /// Port of the server requests are initially sent to.
+    pub server_port: u16,
+    /// Number of attempts of each request.
+    pub try_count: u16,
+    /// Timeout of each attempt in seconds.
+    pub timeout_value: u16,
+}
+
+/// EFI_MTFTP4_OPTION, a NUL terminated option name and value.
+#[repr(C)]
+pub struct Mtftp4Option {
+    /// Name of the option.
+    pub option_str: *const u8,
+    /// Value of the option.
+    pub value_str: *const u8,
+}
+
+/// Iterator over the `(name, value)` options acknowledged by a server in an
+/// OACK packet.
+///
+/// Iteration stops at the end of the packet, at an option missing its NUL
+/// terminator, or at an option that is not valid UTF-8.
+#[derive(Debug, Clone, Copy)]
+pub struct OackOptions<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> OackOptions<'a> {
+
+    /// Parse a raw TFTP packet, returning `None` if it is not an OACK.
+    pub fn parse(packet: &'a [u8]) -> Option<OackOptions<'a>> {
+
+        match packet {
+            [a, b, data @ ..] if u16::from_be_bytes([*a, *b]) == OPCODE_OACK =>
+                Some(OackOptions{ data }),
+            _ => None,
+        }
+
+    }
+
+    /// Returns the value of the option `name`, compared case insensitively.
+    pub fn get(mut self, name: &str) -> Option<&'a str> {
+        self.find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    /// Returns the transfer size acknowledged by the server, see RFC 2349.
+    pub fn tsize(self) -> Option<u64> {
+        self.get("tsize")?.parse().ok()
+    }
+
+    fn next_str(&mut self) -> Option<&'a str> {
+
+        let end = self.data.iter().position(|&b| b == 0)?;
+        let s = str::from_utf8(&self.data[..end]).ok()?;
+        self.data = &self.data[end + 1..];
+        Some(s)
+
+    }
+
+}
+
+impl<'a> Iterator for OackOptions<'a> {
+
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        let name = self.next_str()?;
+        let value = self.next_str()?;
+        Some((name, value))
+
+    }
+
+}
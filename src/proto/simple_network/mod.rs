@@ -0,0 +1,196 @@
+//! Simple network protocol
+
+use crate::proto::{
+    Protocol,
+    dhcp4::MacAddress,
+};
+use crate::{unsafe_guid, Event, Result, Status};
+#[cfg(feature = "exts")]
+use crate::{Handle, table::boot::BootServices};
+#[cfg(feature = "exts")]
+use alloc_api::vec::Vec;
+
+/// The simple network protocol provides a packet level interface to a network
+/// adapter.
+#[repr(C)]
+#[unsafe_guid("a19832b9-ac25-11d3-9a2d-0090273fc14d")]
+#[derive(Protocol)]
+pub struct SimpleNetwork {
+    revision: u64,
+
+    start: extern "efiapi" fn(this: &mut SimpleNetwork) -> Status,
+
+    stop: extern "efiapi" fn(this: &mut SimpleNetwork) -> Status,
+
+    initialize: extern "efiapi" fn(
+        this: &mut SimpleNetwork,
+        extra_rx_buffer_size: usize,
+        extra_tx_buffer_size: usize,
+    ) -> Status,
+
+    reset: usize,
+
+    shutdown: extern "efiapi" fn(this: &mut SimpleNetwork) -> Status,
+
+    receive_filters: usize,
+    station_address: usize,
+    statistics: usize,
+    mcast_ip_to_mac: usize,
+    nv_data: usize,
+    get_status: usize,
+    transmit: usize,
+    receive: usize,
+
+    wait_for_packet: Event,
+    mode: *const Mode,
+}
+
+impl SimpleNetwork {
+
+    /// Changes the state of the network interface from stopped to started.
+    pub fn start(&mut self) -> Result<()> {
+
+        (self.start)(self).into()
+
+    }
+
+    /// Changes the state of the network interface from started to stopped.
+    pub fn stop(&mut self) -> Result<()> {
+
+        (self.stop)(self).into()
+
+    }
+
+    /// Resets the network adapter and allocates the transmit and receive
+    /// buffers required by the network interface. The extra buffer sizes
+    /// request additional space for the driver, zero uses firmware defaults.
+    pub fn initialize(
+        &mut self, extra_rx_buffer_size: usize, extra_tx_buffer_size: usize
+    ) -> Result<()> {
+
+        (self.initialize)(self, extra_rx_buffer_size, extra_tx_buffer_size).into()
+
+    }
+
+    /// Changes the state of the network interface from initialized to
+    /// started, releasing the buffers allocated by `initialize`.
+    pub fn shutdown(&mut self) -> Result<()> {
+
+        (self.shutdown)(self).into()
+
+    }
+
+    /// Starts and initializes the network interface with default buffer
+    /// sizes.
+    ///
+    /// Fails with `ALREADY_STARTED` if the interface is not stopped, and with
+    /// `DEVICE_ERROR` if the interface did not reach the initialized state.
+    pub fn bring_up(&mut self) -> Result<()> {
+
+        if self.mode().state != NetworkState::STOPPED {
+            return Err(Status::ALREADY_STARTED.into());
+        }
+
+        self.start()?.log();
+        self.initialize(0, 0)?.log();
+
+        if self.mode().state != NetworkState::INITIALIZED {
+            return Err(Status::DEVICE_ERROR.into());
+        }
+
+        Ok(Status::SUCCESS.into())
+
+    }
+
+    /// Event used to wait for a packet to be received.
+    pub fn wait_for_packet(&self) -> Event {
+        self.wait_for_packet
+    }
+
+    /// Returns the current mode of the network interface.
+    pub fn mode(&self) -> &Mode {
+        unsafe { &*self.mode }
+    }
+
+}
+
+/// Returns every handle supporting the simple network protocol, along with
+/// the permanent MAC address of its interface.
+///
+/// Handles on which the protocol cannot be opened are skipped.
+#[cfg(feature = "exts")]
+pub fn network_adapters(bt: &BootServices) -> Result<Vec<(Handle, MacAddress)>> {
+
+    let handles = match bt.find_handles::<SimpleNetwork>() {
+        Ok(handles) => handles.log(),
+        Err(e) if e.status() == Status::NOT_FOUND => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    let adapters: Vec<_> = handles
+        .into_iter()
+        .filter_map(|handle| {
+            let snp = bt.handle_protocol::<SimpleNetwork>(handle).ok()?.log();
+            let snp = unsafe { &*snp.get() };
+            Some((handle, snp.mode().permanent_address))
+        })
+        .collect();
+
+    Ok(adapters.into())
+
+}
+
+newtype_enum! {
+/// State of a network interface.
+pub enum NetworkState: u32 => {
+    /// The interface is stopped.
+    STOPPED     = 0,
+    /// The interface is started but not initialized.
+    STARTED     = 1,
+    /// The interface is initialized and ready to send and receive packets.
+    INITIALIZED = 2,
+}}
+
+/// Current state and capabilities of a network interface.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Mode {
+    /// Current state of the interface.
+    pub state: NetworkState,
+    /// Size of the network interface's hardware address in bytes.
+    pub hw_address_size: u32,
+    /// Size of the network interface's media header in bytes.
+    pub media_header_size: u32,
+    /// Maximum size of packets the interface can transmit or receive.
+    pub max_packet_size: u32,
+    /// Size of the NVRAM device attached to the interface in bytes.
+    pub nv_ram_size: u32,
+    /// Size that must be used for all NVRAM reads and writes.
+    pub nv_ram_access_size: u32,
+    /// Multicast receive filter settings supported by the interface.
+    pub receive_filter_mask: u32,
+    /// Current multicast receive filter settings.
+    pub receive_filter_setting: u32,
+    /// Maximum number of multicast addresses that can be received.
+    pub max_mcast_filter_count: u32,
+    /// Current number of multicast addresses in the filter list.
+    pub mcast_filter_count: u32,
+    /// Multicast addresses the interface receives packets for.
+    pub mcast_filter: [MacAddress; 16],
+    /// Current hardware address of the interface.
+    pub current_address: MacAddress,
+    /// Broadcast address of the interface.
+    pub broadcast_address: MacAddress,
+    /// Permanent hardware address of the interface.
+    pub permanent_address: MacAddress,
+    /// Interface type of the network interface.
+    pub if_type: u8,
+    /// True if the hardware address can be changed.
+    pub mac_address_changeable: bool,
+    /// True if the interface can transmit more than one packet at a time.
+    pub multiple_tx_supported: bool,
+    /// True if the presence of media can be determined.
+    pub media_present_supported: bool,
+    /// True if media is connected to the interface.
+    pub media_present: bool,
+}
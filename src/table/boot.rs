@@ -54,14 +54,23 @@ pub struct BootServices {
         events: *mut Event,
         out_index: *mut usize,
     ) -> Status,
-    signal_event: usize,
-    close_event: usize,
+    signal_event: extern "efiapi" fn(event: Event) -> Status,
+    close_event: unsafe extern "efiapi" fn(event: Event) -> Status,
     check_event: usize,
 
     // Protocol handlers
-    install_protocol_interface: usize,
+    install_protocol_interface: unsafe extern "efiapi" fn(
+        handle: &mut Handle,
+        guid: &Guid,
+        interface_type: u32,
+        interface: *mut c_void,
+    ) -> Status,
     reinstall_protocol_interface: usize,
-    uninstall_protocol_interface: usize,
+    uninstall_protocol_interface: unsafe extern "efiapi" fn(
+        handle: Handle,
+        guid: &Guid,
+        interface: *mut c_void,
+    ) -> Status,
     handle_protocol:
         extern "efiapi" fn(handle: Handle, proto: &Guid, out_proto: &mut *mut c_void) -> Status,
     _reserved: usize,
@@ -373,6 +382,24 @@ impl BootServices {
         )
     }
 
+    /// Places an event in the signaled state.
+    ///
+    /// If the event has flag `NotifySignal` set, its notification function is
+    /// enqueued. Signaling an already signaled event has no effect.
+    pub fn signal_event(&self, event: Event) -> Result {
+        (self.signal_event)(event).into()
+    }
+
+    /// Closes an event, removing it from any timer or notification queue.
+    ///
+    /// # Safety
+    ///
+    /// The event must not be used after it is closed, including by any
+    /// protocol it was registered with.
+    pub unsafe fn close_event(&self, event: Event) -> Result {
+        (self.close_event)(event).into()
+    }
+
     /// Sets the trigger for `EventType::TIMER` event.
     pub fn set_timer(&self, event: Event, trigger_time: TimerTrigger) -> Result {
         let (ty, time) = match trigger_time {
@@ -383,6 +410,46 @@ impl BootServices {
         unsafe { (self.set_timer)(event, ty, time) }.into()
     }
 
+    /// Installs a protocol interface on a device handle.
+    ///
+    /// If `handle` is `None`, a new handle is created and returned. Otherwise
+    /// the interface is added to the given handle, which is returned.
+    ///
+    /// # Safety
+    ///
+    /// The interface must point to a valid implementation of the protocol
+    /// identified by `guid`, and must remain valid for as long as the
+    /// protocol stays installed.
+    pub unsafe fn install_protocol_interface(
+        &self,
+        handle: Option<Handle>,
+        guid: &Guid,
+        interface: *mut c_void,
+    ) -> Result<Handle> {
+        let mut handle = handle.unwrap_or_else(|| Handle::uninitialized());
+        // Only native interfaces are defined by the UEFI specification
+        (self.install_protocol_interface)(&mut handle, guid, 0, interface)
+            .into_with_val(|| handle)
+    }
+
+    /// Removes a protocol interface from a device handle.
+    ///
+    /// `interface` must be the pointer the protocol was installed with. If
+    /// it was the last protocol on the handle, the handle is freed.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure no one still uses the interface, as the
+    /// firmware does not check it before removing the protocol.
+    pub unsafe fn uninstall_protocol_interface(
+        &self,
+        handle: Handle,
+        guid: &Guid,
+        interface: *mut c_void,
+    ) -> Result {
+        (self.uninstall_protocol_interface)(handle, guid, interface).into()
+    }
+
     /// Query a handle for a certain protocol.
     ///
     /// This function attempts to get the protocol implementation of a handle,
@@ -661,6 +728,63 @@ impl Drop for TplGuard<'_> {
     }
 }
 
+/// An event for waiting on a signal from a protocol or a notification
+/// function.
+///
+/// The firmware refuses to wait on events of type `NotifySignal`, so the event
+/// has no notification function: protocols taking an event to signal, like
+/// `IP4Config2::register_data_notify`, are handed `event()` and the caller
+/// then blocks in `wait()`.
+///
+/// The event is closed when the guard is dropped.
+pub struct WaitableEvent<'boot> {
+    boot_services: &'boot BootServices,
+    event: Event,
+}
+
+impl<'boot> WaitableEvent<'boot> {
+    /// Creates a new event in the waiting state.
+    pub fn new(boot_services: &'boot BootServices) -> Result<Self> {
+        // An event without notification function cannot run code behind our
+        // back, e.g. after exiting boot services
+        let event =
+            unsafe { boot_services.create_event(EventType::empty(), Tpl::APPLICATION, None) }?;
+        Ok(event.map(|event| WaitableEvent { boot_services, event }))
+    }
+
+    /// The underlying event, to be registered with a protocol.
+    ///
+    /// The event must be unregistered before the guard is dropped.
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    /// Places the event in the signaled state.
+    pub fn signal(&self) -> Result {
+        self.boot_services.signal_event(self.event)
+    }
+
+    /// Blocks until the event is signaled, then clears the signaled state.
+    ///
+    /// Like `BootServices::wait_for_event`, this must be called at
+    /// `Tpl::APPLICATION`.
+    pub fn wait(&self) -> Result {
+        let mut events = [self.event];
+        self.boot_services
+            .wait_for_event(&mut events)
+            .map(|completion| completion.map(|_| ()))
+            .map_err(|err| err.status().into())
+    }
+}
+
+impl Drop for WaitableEvent<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.boot_services.close_event(self.event);
+        }
+    }
+}
+
 /// Type of allocation to perform.
 #[derive(Debug, Copy, Clone)]
 pub enum AllocateType {
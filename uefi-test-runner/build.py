@@ -65,6 +65,13 @@ def esp_dir():
     'Returns the directory where we will build the emulated UEFI system partition'
     return build_dir() / 'esp'
 
+def tftp_dir():
+    'Returns the directory served by the TFTP server of the emulated network'
+    return build_dir() / 'tftp'
+
+# Size of the file served over TFTP, checked by the MTFTP4 tests.
+TFTP_FILE_SIZE = 12345
+
 def run_tool(tool, *flags):
     'Runs cargo-<tool> with certain arguments.'
 
@@ -119,6 +126,10 @@ def build(*test_flags):
 
     shutil.copy2(built_file, output_file)
 
+    # Serve a file of known size over TFTP.
+    tftp_dir().mkdir(parents=True, exist_ok=True)
+    (tftp_dir() / 'tsize.bin').write_bytes(bytes(TFTP_FILE_SIZE))
+
 def clippy():
     'Runs Clippy on all projects'
 
@@ -238,6 +249,16 @@ def run_qemu():
         '-qmp', f'pipe:{qemu_monitor_pipe}',
     ])
 
+    # Connect two network cards, with distinct MAC addresses, to QEMU's user
+    # network, whose gateway serves a directory over TFTP. Each card gets a
+    # network of its own. The cards have no option ROM, the network stack of
+    # the firmware drives them.
+    for i in range(2):
+        qemu_flags.extend([
+            '-netdev', f'user,id=net{i},tftp={tftp_dir()}',
+            '-device', f'virtio-net-pci,netdev=net{i},mac=52:54:00:12:34:{i + 0x56:02x},romfile=',
+        ])
+
     # For now these only work on x86_64
     if arch == 'x86_64':
         # Enable debug features
@@ -1,9 +1,11 @@
 use uefi::prelude::*;
-use uefi::table::boot::{BootServices, EventType, TimerTrigger, Tpl};
+use uefi::table::boot::{BootServices, EventType, TimerTrigger, Tpl, WaitableEvent};
 
 pub fn test(bt: &BootServices) {
     info!("Testing timer...");
     test_timer(bt);
+    info!("Testing waitable event...");
+    test_waitable_event(bt);
     info!("Testing watchdog...");
     test_watchdog(bt);
 }
@@ -23,3 +25,26 @@ fn test_timer(bt: &BootServices) {
     bt.wait_for_event(&mut events)
         .expect_success("Wait for event failed");
 }
+
+fn test_waitable_event(bt: &BootServices) {
+    let event = WaitableEvent::new(bt).expect_success("Failed to create waitable event");
+    event.signal().expect_success("Failed to signal event");
+    event
+        .wait()
+        .expect_success("Wait for signaled event failed");
+
+    // Waiting clears the signaled state, so the event can be reused
+    event
+        .signal()
+        .expect_success("Failed to signal event again");
+    event
+        .wait()
+        .expect_success("Wait for event signaled again failed");
+
+    // Dropping closes the event; creating and dropping many must not exhaust
+    // the firmware's event pool
+    drop(event);
+    for _ in 0..1000 {
+        WaitableEvent::new(bt).expect_success("Failed to create waitable event");
+    }
+}
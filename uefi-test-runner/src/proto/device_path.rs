@@ -0,0 +1,998 @@
+use core::alloc::Layout;
+use core::mem::align_of;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use uefi::prelude::*;
+use uefi::proto::dhcp4::{HardwareType, Ipv4Addr, MacAddress};
+use uefi::proto::loaded_image::{
+    ACPIDevicePath, ACPIPathSubType, BootCategory, ControllerDevicePath, DevicePath, DevicePathBox,
+    DevicePathBuilder, DevicePathError, DevicePathPayload, DevicePathUtilities, DeviceType,
+    EndPathSubType, FileDevicePath, HardDriveDevicePath, HardwarePathSubType, IPProtocol,
+    IPv4DevicePath, IscsiDevicePath, MACDevicePath, MediaPathSubType, MessagingPathSubType,
+    NvmeDevicePath, PCIDevicePath, RamDiskDevicePath, RamDiskKind, SataDevicePath, URIDevicePath,
+    USBDevicePath, VLANDevicePath, VendorDevicePath, ISCSI_PORT, LINUX_INITRD_MEDIA_GUID,
+};
+use uefi::table::boot::BootServices;
+use uefi::Guid;
+
+pub fn test(bt: &BootServices) {
+    info!("Running device path tests");
+    test_first_instance();
+    test_append();
+    test_box_size();
+    test_ensure_terminated();
+    test_new_terminated();
+    test_nodes();
+    test_self_check();
+    test_sub_type_name();
+    test_subtypes();
+    test_vlan_id();
+    test_mac_round_trip();
+    test_linux_initrd();
+    test_mac_ethernet();
+    test_iscsi_path();
+    test_http_boot_path();
+    test_stable_hash();
+    test_uri_debug();
+    test_uri_scheme();
+    test_internal_storage();
+    test_boot_category();
+    test_same_partition();
+    test_hex_dump();
+    test_file_path();
+    test_ram_disk_path();
+    test_controller_path();
+    test_usb_path();
+    test_nvme_path();
+    test_sata_path();
+    test_vendor_path();
+    test_iscsi_target();
+
+    if let Ok(utilities) = bt.locate_protocol::<DevicePathUtilities>() {
+        let utilities =
+            utilities.expect("Warnings encountered while opening device path utilities");
+        let utilities = unsafe { &*utilities.get() };
+
+        test_duplicate(utilities);
+        test_device_path_size(utilities);
+        test_create_node(utilities);
+        test_append_node(utilities);
+        test_instances(utilities);
+    } else {
+        warn!("Device path utilities protocol is not supported");
+    }
+}
+
+fn test_first_instance() {
+    // PCI node, end of instance, PCI node, end of path.
+    let bytes: [u8; 20] = [
+        0x01, 0x01, 6, 0, 0x00, 0x1f,
+        0x7f, 0x01, 4, 0,
+        0x01, 0x01, 6, 0, 0x02, 0x03,
+        0x7f, 0xff, 4, 0,
+    ];
+    let path = unsafe { &*(bytes.as_ptr() as *const DevicePath) };
+
+    let mut nodes = 0;
+    path.first_instance().walk(&mut |node| {
+        assert_eq!(node.payload::<[u8; 2]>(), &[0x00, 0x1f]);
+        nodes += 1;
+    });
+    assert_eq!(nodes, 1);
+    assert_eq!(path.first_instance().as_bytes(), &bytes[..6]);
+}
+
+fn test_append() {
+    let pci = |device| {
+        DevicePath::new1(DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::PCI as u8,
+            PCIDevicePath {
+                function: 0,
+                device,
+            },
+        ))
+    };
+
+    let (a, b) = (pci(0x1f), pci(0x02));
+    let path = DevicePath::append(&a, &b);
+    assert_eq!(path.self_check(), Ok(()));
+    assert_eq!(path.as_bytes().len(), 2 * 6 + 4);
+
+    let mut nodes = alloc::vec::Vec::new();
+    path.walk(&mut |node| nodes.push((node.device_type, node.sub_type)));
+    assert_eq!(
+        &nodes[..],
+        &[
+            (DeviceType::Hardware, HardwarePathSubType::PCI as u8),
+            (DeviceType::Hardware, HardwarePathSubType::PCI as u8),
+            (DeviceType::End, EndPathSubType::EndEntire as u8),
+        ]
+    );
+}
+
+// Size and alignment of the last path freed by `record_dealloc`.
+static FREED_SIZE: AtomicUsize = AtomicUsize::new(0);
+static FREED_ALIGN: AtomicUsize = AtomicUsize::new(0);
+
+unsafe fn record_dealloc(p: *mut u8, layout: Layout) {
+    FREED_SIZE.store(layout.size(), Ordering::SeqCst);
+    FREED_ALIGN.store(layout.align(), Ordering::SeqCst);
+    alloc::alloc::dealloc(p, layout);
+}
+
+/// Drop `path`, returning the layout it was freed with.
+fn freed_layout(mut path: DevicePathBox) -> Layout {
+    unsafe { path.set_dealloc(record_dealloc) };
+    drop(path);
+    Layout::from_size_align(
+        FREED_SIZE.load(Ordering::SeqCst),
+        FREED_ALIGN.load(Ordering::SeqCst),
+    )
+    .unwrap()
+}
+
+fn test_box_size() {
+    let pci = || {
+        DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::PCI as u8,
+            PCIDevicePath {
+                function: 0,
+                device: 0x1f,
+            },
+        )
+    };
+    let allocated = |size| Layout::from_size_align(size, align_of::<DevicePath>()).unwrap();
+
+    // Each box must free exactly the allocation holding all of its nodes,
+    // PCI nodes of 6 bytes followed by an end node of 4.
+    let one = DevicePath::new1(pci());
+    let two = DevicePath::new2(pci(), pci());
+    let appended = DevicePath::append(&one, &two);
+    assert_eq!(freed_layout(appended), allocated(3 * 6 + 4));
+    assert_eq!(freed_layout(two), allocated(2 * 6 + 4));
+    assert_eq!(freed_layout(one), allocated(6 + 4));
+}
+
+fn test_ensure_terminated() {
+    let mut path = DevicePath::new(
+        DeviceType::Hardware,
+        HardwarePathSubType::PCI as u8,
+        PCIDevicePath {
+            function: 0,
+            device: 0x1f,
+        },
+    );
+    assert_eq!(path.size(), 10);
+
+    // Overwrite the end node with an empty hardware node, as faulty code
+    // building a path by hand might.
+    unsafe {
+        let end = (path.as_ptr() as *mut u8).add(6);
+        end.copy_from_nonoverlapping([1u8, 1, 4, 0].as_ptr(), 4);
+    }
+
+    assert_eq!(path.ensure_terminated(), Ok(()));
+    assert_eq!(path.self_check(), Ok(()));
+    assert_eq!(path.size(), 14);
+    assert_eq!(path.as_bytes(), &[1, 1, 6, 0, 0, 0x1f, 1, 1, 4, 0, 0x7f, 0xff, 4, 0]);
+
+    // A terminated path is left as is
+    let p = path.as_ptr();
+    assert_eq!(path.ensure_terminated(), Ok(()));
+    assert_eq!(path.as_ptr(), p);
+    assert_eq!(path.size(), 14);
+}
+
+fn test_new_terminated() {
+    let path = DevicePath::new(
+        DeviceType::Messaging,
+        MessagingPathSubType::USB as u8,
+        USBDevicePath::new(3, 1),
+    );
+    assert_eq!(path.self_check(), Ok(()));
+
+    let mut nodes = alloc::vec::Vec::new();
+    path.walk(&mut |node| nodes.push((node.device_type, node.sub_type, node.len())));
+    assert_eq!(
+        &nodes[..],
+        &[
+            (DeviceType::Messaging, MessagingPathSubType::USB as u8, 6),
+            (DeviceType::End, EndPathSubType::EndEntire as u8, 4),
+        ]
+    );
+    assert_eq!(path.size(), 10);
+}
+
+fn test_nodes() {
+    let path = DevicePath::new2(
+        DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::PCI as u8,
+            PCIDevicePath {
+                function: 0,
+                device: 0x1f,
+            },
+        ),
+        DevicePathPayload::create(
+            DeviceType::Messaging,
+            MessagingPathSubType::VLAN as u8,
+            VLANDevicePath::new(42),
+        ),
+    );
+
+    assert_eq!(path.nodes().count(), 3);
+    let vlan = path
+        .nodes()
+        .find(|n| n.device_type == DeviceType::Messaging)
+        .expect("Failed to find the messaging node");
+    assert_eq!(vlan.sub_type, MessagingPathSubType::VLAN as u8);
+    assert_eq!(
+        path.nodes().last().map(|n| (n.device_type, n.sub_type)),
+        Some((DeviceType::End, EndPathSubType::EndEntire as u8))
+    );
+}
+
+fn test_self_check() {
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Hardware,
+        HardwarePathSubType::PCI as u8,
+        PCIDevicePath {
+            function: 0,
+            device: 0x1f,
+        },
+    ));
+    assert_eq!(path.self_check(), Ok(()));
+    assert!(DevicePath::parse(path.as_bytes()).is_ok());
+
+    // A node claiming to be shorter than its own header.
+    let bytes: [u8; 10] = [0x01, 0x01, 2, 0, 0x00, 0x1f, 0x7f, 0xff, 4, 0];
+    assert_eq!(
+        DevicePath::parse(&bytes).err(),
+        Some(DevicePathError::InvalidNodeLength)
+    );
+
+    // A path without its end node.
+    let bytes: [u8; 6] = [0x01, 0x01, 6, 0, 0x00, 0x1f];
+    assert_eq!(
+        DevicePath::parse(&bytes).err(),
+        Some(DevicePathError::MissingEnd)
+    );
+
+    // A node running past the end of the allocation.
+    let mut path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Hardware,
+        HardwarePathSubType::PCI as u8,
+        PCIDevicePath {
+            function: 0,
+            device: 0x1f,
+        },
+    ));
+    path.length = [0xff, 0x00];
+    assert_eq!(path.self_check(), Err(DevicePathError::MissingEnd));
+
+    // An unknown end node ends the walk, but not the parse.
+    let pci = || {
+        DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::PCI as u8,
+            PCIDevicePath {
+                function: 0,
+                device: 0x1f,
+            },
+        )
+    };
+    let mut path = DevicePath::new2(pci(), pci());
+    let bytes = &mut *path as *mut DevicePath as *mut u8;
+    unsafe {
+        *bytes.add(6) = DeviceType::End as u8;
+        *bytes.add(7) = 0x02;
+    }
+    assert_eq!(path.self_check(), Err(DevicePathError::Mismatch));
+}
+
+fn test_vlan_id() {
+    let mac = || {
+        DevicePathPayload::create(
+            DeviceType::Messaging,
+            MessagingPathSubType::MAC as u8,
+            MACDevicePath::from(MacAddress::from_bytes(&[0x52, 0x54, 0, 0x12, 0x34, 0x56])),
+        )
+    };
+
+    let tagged = DevicePath::new2(
+        mac(),
+        DevicePathPayload::create(
+            DeviceType::Messaging,
+            MessagingPathSubType::VLAN as u8,
+            VLANDevicePath::new(42),
+        ),
+    );
+    assert_eq!(tagged.vlan_id(), Some(42));
+
+    let untagged = DevicePath::new1(mac());
+    assert_eq!(untagged.vlan_id(), None);
+}
+
+fn test_mac_ethernet() {
+    let mac = [0x52, 0x54, 0, 0x12, 0x34, 0x56];
+    let node = MACDevicePath::from_ethernet(mac, HardwareType::Ethernet);
+    assert_eq!(node.mac6(), mac);
+    assert_eq!(&node.address[..6], &mac);
+    assert!(node.address[6..].iter().all(|&b| b == 0));
+
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Messaging,
+        MessagingPathSubType::MAC as u8,
+        node,
+    ));
+    assert_eq!(path.len(), 37);
+    assert_eq!(path.payload::<MACDevicePath>().mac6(), mac);
+}
+
+fn test_http_boot_path() {
+    let mac = MacAddress::from_bytes(&[0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+    let uri = "http://192.168.1.1/boot.efi";
+    let path = DevicePathBuilder::new()
+        .push(DeviceType::Messaging, MessagingPathSubType::MAC as u8, MACDevicePath::from(mac))
+        .push(
+            DeviceType::Messaging,
+            MessagingPathSubType::IPv4 as u8,
+            IPv4DevicePath::new(Ipv4Addr::default(), 0, IPProtocol::TCP),
+        )
+        .push(DeviceType::Messaging, MessagingPathSubType::URI as u8, URIDevicePath { uri })
+        .finish();
+    assert_eq!(path.self_check(), Ok(()));
+
+    let nodes: alloc::vec::Vec<_> = path
+        .nodes()
+        .map(|node| (node.device_type, node.sub_type, node.len()))
+        .collect();
+    assert_eq!(
+        &nodes[..],
+        &[
+            (DeviceType::Messaging, MessagingPathSubType::MAC as u8, 37),
+            (DeviceType::Messaging, MessagingPathSubType::IPv4 as u8, 27),
+            (DeviceType::Messaging, MessagingPathSubType::URI as u8, 4 + uri.len()),
+            (DeviceType::End, EndPathSubType::EndEntire as u8, 4),
+        ]
+    );
+
+    let helper = DevicePathBuilder::http_boot(mac, uri);
+    assert_eq!(helper.as_bytes(), path.as_bytes());
+}
+
+fn test_iscsi_path() {
+    let iqn = "iqn.2003-01.org.linux-iscsi.target:sn.1234";
+    let path = DevicePathBuilder::iscsi(
+        MacAddress::from_bytes(&[0x52, 0x54, 0, 0x12, 0x34, 0x56]),
+        Ipv4Addr::new(192, 168, 1, 10),
+        iqn,
+        [0; 8],
+    );
+    assert_eq!(path.self_check(), Ok(()));
+
+    let mut nodes = alloc::vec::Vec::new();
+    path.walk(&mut |node| nodes.push((node.device_type, node.sub_type, node.len())));
+    assert_eq!(
+        &nodes[..],
+        &[
+            (DeviceType::Messaging, MessagingPathSubType::MAC as u8, 37),
+            (DeviceType::Messaging, MessagingPathSubType::IPv4 as u8, 27),
+            (
+                DeviceType::Messaging,
+                MessagingPathSubType::ISCSI as u8,
+                18 + iqn.len()
+            ),
+            (DeviceType::End, 0xff, 4),
+        ][..]
+    );
+
+    let mut node = &*path;
+    node = node.next().unwrap();
+    let ipv4 = node.payload::<IPv4DevicePath>();
+    assert_eq!(ipv4.remote_ip, [192, 168, 1, 10]);
+    assert_eq!({ ipv4.remote_port }, ISCSI_PORT);
+    assert_eq!(ipv4.ip_protocol(), Some(IPProtocol::TCP));
+
+    node = node.next().unwrap();
+    assert_eq!(&node.as_bytes()[18..node.len()], iqn.as_bytes());
+}
+
+fn test_stable_hash() {
+    let pci = |device| {
+        DevicePath::new1(DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::PCI as u8,
+            PCIDevicePath {
+                function: 0,
+                device,
+            },
+        ))
+    };
+
+    assert_eq!(pci(0x1f).stable_hash(), pci(0x1f).stable_hash());
+    assert_ne!(pci(0x1f).stable_hash(), pci(0x1e).stable_hash());
+}
+
+fn test_uri_debug() {
+    // URI node, end of path.
+    let mut bytes = [0u8; 27];
+    bytes[..4].copy_from_slice(&[0x03, 24, 23, 0]);
+    bytes[4..23].copy_from_slice(b"http://example.com/");
+    bytes[23..].copy_from_slice(&[0x7f, 0xff, 4, 0]);
+    let path = unsafe { &*(bytes.as_ptr() as *const DevicePath) };
+
+    assert_eq!(path.payload_bytes(), b"http://example.com/");
+    let debug = alloc::format!("{:?}", path);
+    info!("URI node: {}", debug);
+    assert!(debug.contains("uri: \"http://example.com/\""));
+}
+
+fn test_uri_scheme() {
+    let allowed = ["http", "https"];
+
+    let http = URIDevicePath {
+        uri: "http://example.com/boot.efi",
+    };
+    assert_eq!(http.scheme(), Some("http"));
+    assert!(http.is_allowed_scheme(&allowed));
+
+    let https = URIDevicePath {
+        uri: "HTTPS://example.com/boot.efi",
+    };
+    assert_eq!(https.scheme(), Some("HTTPS"));
+    assert!(https.is_allowed_scheme(&allowed));
+
+    let file = URIDevicePath {
+        uri: "file:///boot.efi",
+    };
+    assert_eq!(file.scheme(), Some("file"));
+    assert!(!file.is_allowed_scheme(&allowed));
+
+    let relative = URIDevicePath { uri: "boot.efi" };
+    assert_eq!(relative.scheme(), None);
+    assert!(!relative.is_allowed_scheme(&allowed));
+}
+
+fn test_internal_storage() {
+    let root = |builder: DevicePathBuilder| {
+        builder
+            .push(
+                DeviceType::ACPI,
+                ACPIPathSubType::ACPI as u8,
+                ACPIDevicePath { hid: 0x0a03_41d0, uid: 0 },
+            )
+            .push(
+                DeviceType::Hardware,
+                HardwarePathSubType::PCI as u8,
+                PCIDevicePath {
+                    function: 0,
+                    device: 0x04,
+                },
+            )
+    };
+    // Partition 1 of a GPT disk.
+    let mut partition = [0u8; 38];
+    partition[0] = 1;
+    partition[36] = 0x02;
+    partition[37] = 0x02;
+
+    // PciRoot(0)/Pci(0x4,0x0)/NVMe(0x1,...)/HD(1,GPT,...)
+    let nvme = root(DevicePathBuilder::new())
+        .push_bytes(
+            DeviceType::Messaging,
+            MessagingPathSubType::NVME as u8,
+            &[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        )
+        .push_bytes(DeviceType::Media, MediaPathSubType::HardDrive as u8, &partition)
+        .finish();
+    assert!(nvme.is_internal_storage());
+
+    // PciRoot(0)/Pci(0x4,0x0)/USB(0x1,0x0)/HD(1,GPT,...)
+    let usb = root(DevicePathBuilder::new())
+        .push_bytes(DeviceType::Messaging, MessagingPathSubType::USB as u8, &[1, 0])
+        .push_bytes(DeviceType::Media, MediaPathSubType::HardDrive as u8, &partition)
+        .finish();
+    assert!(!usb.is_internal_storage());
+}
+
+fn test_hex_dump() {
+    let path = DevicePath::new2(
+        DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::PCI as u8,
+            PCIDevicePath {
+                function: 0,
+                device: 0x1f,
+            },
+        ),
+        DevicePathPayload::create(
+            DeviceType::Messaging,
+            MessagingPathSubType::VLAN as u8,
+            VLANDevicePath::new(42),
+        ),
+    );
+    assert_eq!(
+        path.hex_dump(),
+        "01 01 06 00  00 1f\n03 14 06 00  2a 00\n7f ff 04 00"
+    );
+}
+
+fn test_file_path() {
+    let file = FileDevicePath::new("\\EFI\\BOOT\\BOOTX64.EFI")
+        .expect_success("Failed to encode the file path");
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Media,
+        MediaPathSubType::File as u8,
+        file,
+    ));
+    assert_eq!(path.self_check(), Ok(()));
+
+    // 21 UCS-2 characters and the NUL terminator.
+    assert_eq!(path.len(), 4 + 22 * 2);
+    let payload = path.payload_bytes();
+    assert_eq!(&payload[..4], [b'\\', 0, b'E', 0]);
+    assert_eq!(&payload[payload.len() - 4..], [b'I', 0, 0, 0]);
+}
+
+fn test_ram_disk_path() {
+    let path = DevicePathBuilder::ram_disk(0x1000_0000, 0x1fff_ffff, RamDiskKind::VirtualCd)
+        .with_file("\\EFI\\BOOT\\BOOTX64.EFI")
+        .expect_success("Failed to encode the file path")
+        .finish();
+    assert_eq!(path.self_check(), Ok(()));
+
+    let nodes: alloc::vec::Vec<_> = path
+        .nodes()
+        .map(|node| (node.device_type, node.sub_type, node.len()))
+        .collect();
+    assert_eq!(
+        &nodes[..],
+        &[
+            (DeviceType::Media, MediaPathSubType::RAMDisk as u8, 38),
+            (DeviceType::Media, MediaPathSubType::File as u8, 4 + 22 * 2),
+            (DeviceType::End, EndPathSubType::EndEntire as u8, 4),
+        ]
+    );
+
+    let ram_disk = path.payload::<RamDiskDevicePath>();
+    assert_eq!(
+        ({ ram_disk.starting_address }, { ram_disk.ending_address }),
+        (0x1000_0000, 0x1fff_ffff)
+    );
+    assert_eq!(ram_disk.kind(), Some(RamDiskKind::VirtualCd));
+}
+
+fn test_sub_type_name() {
+    let pci = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Hardware,
+        HardwarePathSubType::PCI as u8,
+        PCIDevicePath {
+            function: 0,
+            device: 0x1f,
+        },
+    ));
+    assert_eq!(pci.sub_type_name(), Some("PCI"));
+
+    let ipv4 = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Messaging,
+        MessagingPathSubType::IPv4 as u8,
+        IPv4DevicePath::new(Ipv4Addr::new(192, 168, 1, 10), 80, IPProtocol::TCP),
+    ));
+    assert_eq!(ipv4.sub_type_name(), Some("IPv4"));
+
+    // 0x7e is not a hardware sub-type
+    let unknown = DevicePath::new1(DevicePathPayload::create(DeviceType::Hardware, 0x7e, ()));
+    assert_eq!(unknown.sub_type_name(), None);
+}
+
+fn test_controller_path() {
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Hardware,
+        HardwarePathSubType::Controller as u8,
+        ControllerDevicePath::new(2),
+    ));
+    assert_eq!(path.self_check(), Ok(()));
+    assert_eq!(path.len(), 8);
+    assert_eq!(path.payload_bytes(), &[2, 0, 0, 0]);
+
+    let controller = path.payload::<ControllerDevicePath>();
+    assert_eq!({ controller.controller_number }, 2);
+
+    let debug = alloc::format!("{:?}", *path);
+    assert!(debug.contains("controller_number: 2"), "{}", debug);
+}
+
+fn test_subtypes() {
+    let messaging = DeviceType::Messaging.subtypes();
+    for &(sub_type, name) in &[
+        (MessagingPathSubType::MAC, "MAC"),
+        (MessagingPathSubType::IPv4, "IPv4"),
+        (MessagingPathSubType::URI, "URI"),
+        (MessagingPathSubType::USB, "USB"),
+    ] {
+        assert!(messaging.contains(&(sub_type as u8, name)), "{}", name);
+    }
+    assert_eq!(messaging.len(), 27);
+
+    // Sub-types are listed in declaration order
+    let end = DeviceType::End.subtypes();
+    assert_eq!(end, &[(0x01, "EndInstance"), (0xff, "EndEntire")]);
+    assert_eq!(DeviceType::Hardware.subtypes()[0], (1, "PCI"));
+}
+
+fn test_usb_path() {
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Messaging,
+        MessagingPathSubType::USB as u8,
+        USBDevicePath::new(3, 1),
+    ));
+    assert_eq!(path.self_check(), Ok(()));
+    assert_eq!(path.len(), 6);
+
+    let usb = path.payload::<USBDevicePath>();
+    assert_eq!((usb.parent_port, usb.interface), (3, 1));
+
+    let debug = alloc::format!("{:?}", *path);
+    info!("USB node: {}", debug);
+    assert!(debug.contains("parent_port: 3, interface: 1"));
+}
+
+fn test_nvme_path() {
+    let eui = [0x00, 0x25, 0x38, 0x5b, 0x71, 0xb0, 0x12, 0x34];
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Messaging,
+        MessagingPathSubType::NVME as u8,
+        NvmeDevicePath::new(0x10, eui),
+    ));
+    assert_eq!(path.self_check(), Ok(()));
+    assert_eq!(path.len(), 16);
+
+    let nvme = path.payload::<NvmeDevicePath>();
+    assert_eq!({ nvme.namespace_id }, 0x10);
+    assert_eq!(nvme.namespace_uuid, eui);
+
+    let debug = alloc::format!("{:?}", *path);
+    info!("NVMe node: {}", debug);
+    assert!(debug.contains("namespace_id: 0x10"));
+}
+
+fn test_sata_path() {
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Messaging,
+        MessagingPathSubType::SATA as u8,
+        SataDevicePath::new(2, 0xffff, 0),
+    ));
+    assert_eq!(path.self_check(), Ok(()));
+    assert_eq!(path.len(), 10);
+
+    let sata = path.payload::<SataDevicePath>();
+    assert_eq!(
+        ({ sata.hba_port }, { sata.port_multiplier_port }, { sata.lun }),
+        (2, 0xffff, 0)
+    );
+}
+
+fn test_vendor_path() {
+    let guid = Guid::from_values(
+        0x12345678,
+        0x9abc,
+        0xdef0,
+        0x0123,
+        [0x45, 0x67, 0x89, 0xab, 0xcd, 0xef],
+    );
+    let vendor = VendorDevicePath::new(guid, &[1, 2, 3]);
+    assert_eq!(vendor.guid(), guid);
+    assert_eq!(vendor.data(), &[1, 2, 3]);
+
+    let debug = alloc::format!("{:?}", vendor);
+    assert!(debug.contains("12345678-9abc-def0-0123-456789abcdef"), "{}", debug);
+
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Hardware,
+        HardwarePathSubType::Vendor as u8,
+        vendor,
+    ));
+    assert_eq!(path.self_check(), Ok(()));
+    assert_eq!(path.len(), 4 + 16 + 3);
+    assert_eq!(path.vendor_data(), Some((guid, &[1u8, 2, 3][..])));
+
+    // Only vendor nodes carry vendor data
+    let pci = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Hardware,
+        HardwarePathSubType::PCI as u8,
+        PCIDevicePath {
+            function: 0,
+            device: 0x1f,
+        },
+    ));
+    assert_eq!(pci.vendor_data(), None);
+}
+
+fn test_iscsi_target() {
+    let iqn = "iqn.2003-01.org.linux-iscsi.target:sn.1234";
+    let mut iscsi = IscsiDevicePath::new([0, 1, 0, 0, 0, 0, 0, 0], iqn);
+    iscsi.set_target_portal_group_tag(3);
+    assert_eq!(iscsi.protocol(), 0);
+    assert_eq!(iscsi.login_options(), 0);
+    assert_eq!(iscsi.lun(), [0, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(iscsi.target_portal_group_tag(), 3);
+    assert_eq!(iscsi.target_name(), iqn);
+
+    // The length of the node accounts for the target name.
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Messaging,
+        MessagingPathSubType::ISCSI as u8,
+        iscsi,
+    ));
+    assert_eq!(path.self_check(), Ok(()));
+    assert_eq!(path.len(), 4 + 14 + iqn.len());
+
+    let payload = path.payload_bytes();
+    assert_eq!(&payload[4..12], &[0, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(&payload[12..14], &[3, 0]);
+    assert_eq!(&payload[14..], iqn.as_bytes());
+}
+
+fn test_duplicate(utilities: &DevicePathUtilities) {
+    let path = DevicePath::new2(
+        DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::PCI as u8,
+            PCIDevicePath {
+                function: 0,
+                device: 0x1f,
+            },
+        ),
+        DevicePathPayload::create(
+            DeviceType::Messaging,
+            MessagingPathSubType::VLAN as u8,
+            VLANDevicePath::new(42),
+        ),
+    );
+
+    let copy = utilities.duplicate(&path);
+    assert_ne!(copy.as_ptr(), path.as_ptr());
+    assert_eq!(copy.as_bytes(), path.as_bytes());
+    assert_eq!(copy.size(), path.size());
+
+    let appended = utilities.append_device_path(&path, &copy);
+    assert_eq!(appended.as_bytes(), DevicePath::append(&path, &copy).as_bytes());
+    assert_eq!(appended.size(), appended.as_bytes().len());
+}
+
+fn test_device_path_size(utilities: &DevicePathUtilities) {
+    let path = DevicePathBuilder::iscsi(
+        MacAddress::from_bytes(&[0x52, 0x54, 0, 0x12, 0x34, 0x56]),
+        Ipv4Addr::new(192, 168, 1, 10),
+        "iqn.2003-01.org.linux-iscsi.target:sn.1234",
+        [0; 8],
+    );
+
+    let mut size = 0;
+    path.walk(&mut |node| size += node.len());
+    assert_eq!(utilities.device_path_size(&path), size);
+}
+
+fn test_create_node(utilities: &DevicePathUtilities) {
+    let node =
+        utilities.create_node(DeviceType::Hardware, HardwarePathSubType::PCI as u8, &[0, 0x1f]);
+    assert_eq!(node.device_type, DeviceType::Hardware);
+    assert_eq!(node.sub_type, HardwarePathSubType::PCI as u8);
+    assert_eq!(node.len(), 6);
+    assert_eq!(node.payload_bytes(), [0, 0x1f]);
+
+    // A header only node.
+    let node = utilities.create_node(DeviceType::End, EndPathSubType::EndEntire as u8, &[]);
+    assert_eq!(node.len(), 4);
+    assert!(node.payload_bytes().is_empty());
+}
+
+fn test_append_node(utilities: &DevicePathUtilities) {
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Hardware,
+        HardwarePathSubType::PCI as u8,
+        PCIDevicePath {
+            function: 0,
+            device: 0x1f,
+        },
+    ));
+    let vlan = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Messaging,
+        MessagingPathSubType::VLAN as u8,
+        VLANDevicePath::new(42),
+    ));
+
+    let appended = utilities.append_node(&path, &vlan);
+    assert_eq!(appended.self_check(), Ok(()));
+    assert_eq!(appended.size(), appended.as_bytes().len());
+    assert_eq!(appended.nodes().count(), 3);
+    assert_eq!(appended.vlan_id(), Some(42));
+}
+
+fn test_instances(utilities: &DevicePathUtilities) {
+    let pci = |device| {
+        DevicePath::new1(DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::PCI as u8,
+            PCIDevicePath {
+                function: 0,
+                device,
+            },
+        ))
+    };
+
+    let (a, b) = (pci(0x1f), pci(0x02));
+    assert!(!utilities.is_multi_instance(&a));
+
+    let path = utilities.append_instance(&a, &b);
+    assert_eq!(path.self_check(), Ok(()));
+    assert!(utilities.is_multi_instance(&path));
+
+    let mut instances = utilities.instances(&path);
+    let first = instances.next().expect("Missing the first instance");
+    assert_eq!(first.as_bytes(), a.as_bytes());
+    let second = instances.next().expect("Missing the second instance");
+    assert_eq!(second.as_bytes(), b.as_bytes());
+    assert!(instances.next().is_none());
+}
+
+fn test_mac_round_trip() {
+    let mac = MacAddress::from_bytes(&[0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+    let node = MACDevicePath::from(mac);
+    assert_eq!(node.mac(), mac);
+
+    let path = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Messaging,
+        MessagingPathSubType::MAC as u8,
+        node,
+    ));
+    assert_eq!(path.payload::<MACDevicePath>().mac(), mac);
+}
+
+fn test_linux_initrd() {
+    let vendor = |guid| {
+        DevicePath::new1(DevicePathPayload::create(
+            DeviceType::Media,
+            MediaPathSubType::Vendor as u8,
+            guid,
+        ))
+    };
+
+    assert!(vendor(LINUX_INITRD_MEDIA_GUID).is_linux_initrd());
+
+    let other = Guid::from_values(0x12345678, 0x9abc, 0xdef0, 0x0123, [0, 1, 2, 3, 4, 5]);
+    assert!(!vendor(other).is_linux_initrd());
+
+    // The node is found behind the nodes of the device providing it.
+    let pci = || {
+        DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::PCI as u8,
+            PCIDevicePath {
+                function: 0,
+                device: 0x1f,
+            },
+        )
+    };
+    let prefixed = DevicePath::new2(
+        pci(),
+        DevicePathPayload::create(
+            DeviceType::Media,
+            MediaPathSubType::Vendor as u8,
+            LINUX_INITRD_MEDIA_GUID,
+        ),
+    );
+    assert!(prefixed.is_linux_initrd());
+
+    // Only a media vendor node carries the initrd.
+    let hardware = DevicePath::new2(
+        pci(),
+        DevicePathPayload::create(
+            DeviceType::Hardware,
+            HardwarePathSubType::Vendor as u8,
+            LINUX_INITRD_MEDIA_GUID,
+        ),
+    );
+    assert!(!hardware.is_linux_initrd());
+}
+
+fn test_boot_category() {
+    // Partition 1 of a GPT disk.
+    let mut partition = [0u8; 38];
+    partition[0] = 1;
+    partition[36] = 0x02;
+    partition[37] = 0x02;
+
+    // Sata(0x0,0xFFFF,0x0)/HD(1,GPT,...)
+    let disk = DevicePathBuilder::new()
+        .push(
+            DeviceType::Messaging,
+            MessagingPathSubType::SATA as u8,
+            SataDevicePath::new(0, 0xffff, 0),
+        )
+        .push_bytes(DeviceType::Media, MediaPathSubType::HardDrive as u8, &partition)
+        .finish();
+    assert_eq!(disk.boot_category(), BootCategory::LocalDisk);
+
+    // Sata(0x1,0xFFFF,0x0)/CDROM(0x0,0x0,0x0), optical wins over the disk.
+    let optical = DevicePathBuilder::new()
+        .push(
+            DeviceType::Messaging,
+            MessagingPathSubType::SATA as u8,
+            SataDevicePath::new(1, 0xffff, 0),
+        )
+        .push_bytes(DeviceType::Media, MediaPathSubType::CDROM as u8, &[0; 20])
+        .finish();
+    assert_eq!(optical.boot_category(), BootCategory::Optical);
+
+    // MAC(525400123456,0x1)/IPv4(0.0.0.0)
+    let network = DevicePathBuilder::new()
+        .push(
+            DeviceType::Messaging,
+            MessagingPathSubType::MAC as u8,
+            MACDevicePath::from_ethernet([0x52, 0x54, 0, 0x12, 0x34, 0x56], HardwareType::Ethernet),
+        )
+        .push(
+            DeviceType::Messaging,
+            MessagingPathSubType::IPv4 as u8,
+            IPv4DevicePath::new(Ipv4Addr::default(), 0, IPProtocol::TCP),
+        )
+        .finish();
+    assert_eq!(network.boot_category(), BootCategory::Network);
+
+    // USB(0x1,0x0)/HD(1,GPT,...), removable wins over the partition.
+    let removable = DevicePathBuilder::new()
+        .push(DeviceType::Messaging, MessagingPathSubType::USB as u8, USBDevicePath::new(1, 0))
+        .push_bytes(DeviceType::Media, MediaPathSubType::HardDrive as u8, &partition)
+        .finish();
+    assert_eq!(removable.boot_category(), BootCategory::Removable);
+
+    // PciRoot(0x0)
+    let other = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::ACPI,
+        ACPIPathSubType::ACPI as u8,
+        ACPIDevicePath { hid: 0x0a03_41d0, uid: 0 },
+    ));
+    assert_eq!(other.boot_category(), BootCategory::Other);
+}
+
+fn test_same_partition() {
+    let partition = |signature| HardDriveDevicePath {
+        partition_number: 1,
+        partition_start: 2048,
+        partition_size: 0x10_0000,
+        partition_signature: signature,
+        partition_format: 0x02,
+        signature_type: 0x02,
+    };
+    let media = MediaPathSubType::HardDrive as u8;
+
+    // Sata(0x0,0xFFFF,0x0)/HD(1,GPT,...)
+    let full = DevicePathBuilder::new()
+        .push(
+            DeviceType::Messaging,
+            MessagingPathSubType::SATA as u8,
+            SataDevicePath::new(0, 0xffff, 0),
+        )
+        .push(DeviceType::Media, media, partition([0x11; 16]))
+        .finish();
+
+    // HD(1,GPT,...), the short form of the same partition.
+    let short = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Media,
+        media,
+        partition([0x11; 16]),
+    ));
+    assert_eq!(short.partition_guid(), full.partition_guid());
+    assert!(short.refers_to_same_partition(&full));
+    assert!(full.refers_to_same_partition(&short));
+
+    let other = DevicePath::new1(DevicePathPayload::create(
+        DeviceType::Media,
+        media,
+        partition([0x22; 16]),
+    ));
+    assert!(!other.refers_to_same_partition(&full));
+}
@@ -0,0 +1,105 @@
+use uefi::prelude::*;
+use uefi::proto::loaded_image::{
+    device_path_text, DevicePath, DevicePathToText, LoadedImage, LOADED_IMAGE_PROTOCOL_REVISION,
+};
+
+pub fn test(image: Handle, bt: &BootServices) {
+    info!("Running loaded image protocol test");
+
+    let loaded_image = bt
+        .handle_protocol::<LoadedImage>(image)
+        .expect_success("Failed to open LoadedImage protocol");
+    let loaded_image = unsafe { &mut *loaded_image.get() };
+
+    test_revision(loaded_image);
+    test_device_path_text(image, loaded_image, bt);
+    test_device_path_to_text(loaded_image, bt);
+    test_load_options(loaded_image);
+}
+
+fn test_device_path_text(image: Handle, loaded_image: &LoadedImage, bt: &BootServices) {
+    let text = device_path_text(bt, loaded_image.device())
+        .expect_success("Failed to convert the device path of the image device to text");
+    info!("- Device: {}", text);
+    assert!(!text.is_empty());
+
+    // The image handle carries no device path of its own.
+    let ret = device_path_text(bt, image);
+    assert_eq!(
+        ret.map(|_| ()).map_err(|err| err.status()),
+        Err(Status::UNSUPPORTED)
+    );
+}
+
+fn test_device_path_to_text(loaded_image: &LoadedImage, bt: &BootServices) {
+    let to_text = match bt.locate_protocol::<DevicePathToText>() {
+        Ok(to_text) => to_text.expect("Warnings encountered while opening device path to text"),
+        Err(_) => {
+            warn!("Device path to text protocol is not supported");
+            return;
+        }
+    };
+    let to_text = unsafe { &*to_text.get() };
+
+    let path = bt
+        .handle_protocol::<DevicePath>(loaded_image.device())
+        .expect_success("Failed to open the device path of the image device");
+    let path = unsafe { &*path.get() };
+
+    let path_text = to_text
+        .path_to_string(bt, path, false, false)
+        .expect_success("Failed to convert device path to text");
+    let node_text = to_text
+        .node_to_string(bt, path, false, false)
+        .expect_success("Failed to convert device node to text");
+    info!("- Device path: {}", path_text);
+
+    // Path text joins the text of its nodes with `/`
+    assert!(!node_text.is_empty());
+    assert!(path_text.starts_with(&node_text), "{} {}", path_text, node_text);
+}
+
+fn test_revision(loaded_image: &LoadedImage) {
+    let revision = loaded_image.revision();
+    info!("- Revision: {:#x}", revision);
+
+    let (major, minor) = LoadedImage::decode_revision(revision);
+    assert!(loaded_image.is_revision_at_least(1, 0));
+    assert!(loaded_image.is_revision_at_least(major, minor));
+    assert!(!loaded_image.is_revision_at_least(major + 1, 0));
+
+    assert_eq!(LoadedImage::decode_revision(0x0001_0000), (1, 0));
+    assert_eq!(LoadedImage::decode_revision(LOADED_IMAGE_PROTOCOL_REVISION), (1, 0));
+    assert_eq!(LoadedImage::decode_revision(0x0002_001e), (2, 30));
+}
+
+fn test_load_options(loaded_image: &mut LoadedImage) {
+    let options = "kernel.efi root=/dev/sda1 quiet";
+    loaded_image
+        .set_load_options(options)
+        .expect("Failed to set load options");
+    // The size is in bytes, for UCS-2 code units followed by a NUL.
+    assert_eq!(
+        loaded_image.load_options_size() as usize,
+        (options.len() + 1) * 2
+    );
+
+    let mut buffer = [0u8; 64];
+    let read = loaded_image
+        .load_options(&mut buffer)
+        .expect("Failed to read load options");
+    assert_eq!(read, options);
+
+    let owned = loaded_image
+        .load_options_string()
+        .expect("Failed to read load options");
+    assert_eq!(owned, options);
+
+    loaded_image
+        .set_load_options("")
+        .expect("Failed to set load options");
+    let owned = loaded_image
+        .load_options_string()
+        .expect("Failed to read load options");
+    assert_eq!(owned, "");
+}
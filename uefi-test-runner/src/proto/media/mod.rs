@@ -1,5 +1,10 @@
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::ptr;
 use uefi::prelude::*;
+use uefi::proto::loaded_image::DevicePath;
 use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::proto::media::loadfile::{InitrdLoadFile, LoadFile};
 
 pub fn test(bt: &BootServices) {
     info!("Testing Media Access protocols");
@@ -32,4 +37,104 @@ pub fn test(bt: &BootServices) {
     } else {
         warn!("`SimpleFileSystem` protocol is not available");
     }
+
+    test_initrd_load_file(bt);
+    test_load_file(bt);
+}
+
+fn test_initrd_load_file(bt: &BootServices) {
+    static INITRD: [u8; 6] = *b"initrd";
+
+    let handle =
+        InitrdLoadFile::install(bt, &INITRD).expect_success("Failed to install initrd provider");
+    let load_file = bt
+        .handle_protocol::<LoadFile<DevicePath>>(handle)
+        .expect_success("Failed to open LoadFile protocol");
+    let load_file = unsafe { &mut *load_file.get() };
+
+    // The remaining path passed to the provider is the end node of its
+    // device path.
+    let mut end: [u8; 4] = [0x7f, 0xff, 4, 0];
+    let end = unsafe { &mut *(end.as_mut_ptr() as *mut DevicePath) };
+
+    // Like the Linux EFI stub, first learn the size with a null buffer...
+    let mut size = 0u64;
+    let ret = load_file.load_file(end, false, &mut size, ptr::null_mut());
+    assert_eq!(
+        ret.map_err(|err| err.status()),
+        Err(Status::BUFFER_TOO_SMALL)
+    );
+    assert_eq!(size, INITRD.len() as u64);
+
+    // ...then load into a buffer of that size.
+    let mut buffer = [0u8; 6];
+    load_file
+        .load_file(end, false, &mut size, buffer.as_mut_ptr() as *mut c_void)
+        .expect_success("Failed to load initrd");
+    assert_eq!(size, INITRD.len() as u64);
+    assert_eq!(buffer, INITRD);
+}
+
+fn test_load_file(bt: &BootServices) {
+    static INITRD: [u8; 6] = *b"initrd";
+
+    let (load_file, path) = install_initrd(bt, &INITRD);
+    let load_file = unsafe { &mut *load_file.get() };
+    let path = unsafe { &mut *path.get() };
+
+    // The remaining path passed to the provider is the end node of its
+    // device path.
+    let mut end: [u8; 4] = [0x7f, 0xff, 4, 0];
+    let end = unsafe { &mut *(end.as_mut_ptr() as *mut DevicePath) };
+
+    // The initrd provider only serves direct loads.
+    let data = load_file
+        .load_direct(end)
+        .expect_success("Failed to load initrd");
+    assert_eq!(&data[..], &INITRD[..]);
+
+    match load_file.load_for_boot(end) {
+        Err(error) => assert_eq!(error.status(), Status::UNSUPPORTED),
+        Ok(_) => panic!("Initrd provider accepted a boot policy load"),
+    }
+
+    // Any other path is not found.
+    let present = load_file
+        .is_present(path, false)
+        .expect_success("Failed to probe initrd");
+    assert!(!present);
+    let present = load_file
+        .is_present(end, false)
+        .expect_success("Failed to probe initrd");
+    assert!(present);
+
+    // A provider without data reports a zero size.
+    let (empty, _) = install_initrd(bt, &[]);
+    let empty = unsafe { &mut *empty.get() };
+    let present = empty
+        .is_present(end, false)
+        .expect_success("Failed to probe empty initrd");
+    assert!(!present);
+    let data = empty
+        .load_direct(end)
+        .expect_success("Failed to load empty initrd");
+    assert!(data.is_empty());
+}
+
+fn install_initrd<'a>(
+    bt: &'a BootServices,
+    data: &'static [u8],
+) -> (
+    &'a UnsafeCell<LoadFile<DevicePath>>,
+    &'a UnsafeCell<DevicePath>,
+) {
+    let handle =
+        InitrdLoadFile::install(bt, data).expect_success("Failed to install initrd provider");
+    let load_file = bt
+        .handle_protocol::<LoadFile<DevicePath>>(handle)
+        .expect_success("Failed to open LoadFile protocol");
+    let path = bt
+        .handle_protocol::<DevicePath>(handle)
+        .expect_success("Failed to open device path protocol");
+    (load_file, path)
 }
@@ -2,7 +2,7 @@ use uefi::prelude::*;
 
 use uefi::proto;
 
-pub fn test(st: &SystemTable<Boot>) {
+pub fn test(image: Handle, st: &SystemTable<Boot>) {
     info!("Testing various protocols");
 
     let bt = st.boot_services();
@@ -11,7 +11,10 @@ pub fn test(st: &SystemTable<Boot>) {
 
     console::test(st);
     debug::test(bt);
+    loaded_image::test(image, bt);
+    device_path::test(bt);
     media::test(bt);
+    network::test(bt);
     pi::test(bt);
 }
 
@@ -30,5 +33,8 @@ fn find_protocol(bt: &BootServices) {
 
 mod console;
 mod debug;
+mod device_path;
+mod loaded_image;
 mod media;
+mod network;
 mod pi;
@@ -0,0 +1,868 @@
+use core::convert::TryFrom;
+use core::mem::size_of;
+use core::ptr;
+use core::time::Duration;
+use uefi::prelude::*;
+use uefi::proto::dhcp4::{
+    options, parse_boot_uri, CallbackAction, CallbackConfig, ConfigData, ConfigDataBuilder, Event,
+    Header, IPv4Address, Ipv4Addr, Ipv4Range, MacAddress, ModeData, OptionIterator, Packet, Scheme,
+    State, DHCP4, INFINITE_LEASE, MAX_HOPS,
+};
+use uefi::proto::ip4_config2::{DataType, IP4Config2, Policy};
+use uefi::proto::loaded_image::IPProtocol;
+use uefi::proto::mtftp4::{ConfigData as Mtftp4ConfigData, Mtftp4, OackOptions};
+use uefi::proto::simple_network::{network_adapters, NetworkState, SimpleNetwork};
+use uefi::table::boot::BootServices;
+
+pub fn test(bt: &BootServices) {
+    info!("Running network protocol tests");
+    test_ip_protocol_names();
+    test_ipv4_conversions();
+    test_ipv4_range();
+    test_multicast_mac();
+    test_concatenated_option();
+    test_options_bounded();
+    test_options_map();
+    test_option_lengths();
+    test_server_id();
+    test_offered_address();
+    test_domain_name();
+    test_tftp_server_name();
+    test_pxe_reply();
+    test_ntp_servers();
+    test_parse_boot_uri();
+    test_pad_run();
+    test_renewing_states();
+    test_configured_states();
+    test_state_round_trip();
+    test_invalid_events();
+    test_magic_cookie();
+    test_boot_filename();
+    test_config_builder();
+    test_callback_trampoline();
+    test_options_write();
+    test_subnet_mask();
+    test_lease_thresholds();
+    test_hops();
+    test_header_addrs();
+    test_ip4_config2_discriminants();
+    test_oack_tsize();
+    test_network_adapters(bt);
+
+    if let Ok(ip4_config2) = bt.locate_protocol::<IP4Config2>() {
+        let ip4_config2 =
+            ip4_config2.expect("Warnings encountered while opening IP4 config2 protocol");
+        let ip4_config2 = unsafe { &mut *ip4_config2.get() };
+
+        test_policy(ip4_config2);
+    } else {
+        warn!("IP4 config2 protocol is not supported");
+    }
+
+    if let Ok(mtftp4) = bt.locate_protocol::<Mtftp4>() {
+        let mtftp4 = mtftp4.expect("Warnings encountered while opening MTFTP4 protocol");
+        let mtftp4 = unsafe { &mut *mtftp4.get() };
+
+        test_get_file_size(mtftp4, bt);
+    } else {
+        warn!("MTFTP4 protocol is not supported");
+    }
+
+    if let Ok(dhcp4) = bt.locate_protocol::<DHCP4>() {
+        let dhcp4 = dhcp4.expect("Warnings encountered while opening DHCP4 protocol");
+        let dhcp4 = unsafe { &mut *dhcp4.get() };
+
+        test_capture_negotiation(dhcp4);
+        test_update_callback(dhcp4);
+        test_inform(dhcp4, bt);
+        test_release_to_server(dhcp4, bt);
+    } else {
+        warn!("DHCP4 protocol is not supported");
+    }
+
+    // Taking the interface down disturbs the network stack using it, so
+    // this runs last.
+    if let Ok(snp) = bt.locate_protocol::<SimpleNetwork>() {
+        let snp = snp.expect("Warnings encountered while opening simple network protocol");
+        let snp = unsafe { &mut *snp.get() };
+
+        test_bring_up(snp);
+    } else {
+        warn!("Simple network protocol is not supported");
+    }
+}
+
+fn test_ip_protocol_names() {
+    assert_eq!(IPProtocol::TCP.name(), "TCP");
+    assert_eq!(IPProtocol::UDP.name(), "UDP");
+    assert_eq!(IPProtocol::from_name("TCP"), Some(IPProtocol::TCP));
+    assert_eq!(IPProtocol::from_name("udp"), Some(IPProtocol::UDP));
+    assert_eq!(IPProtocol::from_name("NOT-A-PROTOCOL"), None);
+}
+
+fn test_ipv4_conversions() {
+    let from_octets: Ipv4Addr = [10, 0, 0, 1].into();
+    let from_parts = Ipv4Addr::new(10, 0, 0, 1);
+    assert_eq!(from_octets, from_parts);
+
+    let wire: IPv4Address = from_parts.into();
+    assert_eq!(wire, [10, 0, 0, 1]);
+    assert_eq!(u32::from(from_parts), 0x0a00_0001);
+    assert_eq!("10.0.0.1".parse::<Ipv4Addr>(), Ok(from_parts));
+
+    let broadcast: Ipv4Addr = "10.0.0.255".parse().expect("Failed to parse address");
+    assert_eq!(broadcast, Ipv4Addr::new(10, 0, 0, 255));
+    assert_eq!(alloc::format!("{}", broadcast), "10.0.0.255");
+}
+
+fn test_ipv4_range() {
+    let addr = Ipv4Addr::new(10, 0, 0, 13);
+    assert_eq!(Ipv4Range::hosts(addr, 29).count(), 6);
+    assert_eq!(
+        Ipv4Range::hosts(addr, 29).next(),
+        Some(Ipv4Addr::new(10, 0, 0, 9))
+    );
+    assert_eq!(
+        Ipv4Range::hosts(addr, 29).last(),
+        Some(Ipv4Addr::new(10, 0, 0, 14))
+    );
+
+    let top = Ipv4Range::new(
+        Ipv4Addr::new(255, 255, 255, 254),
+        Ipv4Addr::new(255, 255, 255, 255),
+    );
+    assert_eq!(top.count(), 2);
+}
+
+fn test_multicast_mac() {
+    let group_mac = MacAddress::from_bytes(&[0x01, 0x00, 0x5e, 0x01, 0x02, 0x03]);
+    assert_eq!(Ipv4Addr::new(239, 1, 2, 3).multicast_mac(), Some(group_mac));
+
+    // Only the low 23 bits of the group are mapped.
+    assert_eq!(Ipv4Addr::new(224, 129, 2, 3).multicast_mac(), Some(group_mac));
+
+    assert_eq!(Ipv4Addr::new(10, 0, 0, 1).multicast_mac(), None);
+}
+
+fn test_concatenated_option() {
+    // A boot file name split across two instances of option 67.
+    let opts: [u8; 14] = [
+        options::BOOTFILE_NAME, 5, b'h', b't', b't', b'p', b':',
+        options::PAD,
+        options::BOOTFILE_NAME, 3, b'/', b'/', b'x',
+        options::END,
+    ];
+
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &opts);
+
+    assert_eq!(
+        packet.concatenated_option(options::BOOTFILE_NAME).as_deref(),
+        Some(&b"http://x"[..])
+    );
+    assert_eq!(packet.concatenated_option(options::ROUTER), None);
+}
+
+fn test_options_map() {
+    let opts: [u8; 19] = [
+        options::ROUTER, 4, 10, 0, 0, 1,
+        options::SUBNET_MASK, 4, 255, 255, 255, 0,
+        options::ROUTER, 4, 10, 0, 0, 2,
+        options::END,
+    ];
+
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &opts);
+
+    let map = packet.options_map();
+    assert_eq!(map.len(), 2);
+    let codes: alloc::vec::Vec<u8> = map.keys().copied().collect();
+    assert_eq!(codes, [options::SUBNET_MASK, options::ROUTER]);
+    assert_eq!(map[&options::SUBNET_MASK], [255, 255, 255, 0]);
+    assert_eq!(map[&options::ROUTER], [10, 0, 0, 1, 10, 0, 0, 2]);
+}
+
+fn test_option_lengths() {
+    assert_eq!(options::expected_len(options::SUBNET_MASK), Some(4));
+    assert_eq!(options::expected_len(options::MESSAGE_TYPE), Some(1));
+    assert_eq!(options::expected_len(options::DOMAIN_NAME), None);
+
+    let opts: [u8; 13] = [
+        options::MESSAGE_TYPE, 1, 5,
+        options::IP_ADDRESS_LEASE_TIME, 4, 0, 0, 0x0e, 0x10,
+        options::END, 0, 0, 0,
+    ];
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &opts);
+    assert_eq!(packet.validate_option_lengths(), Ok(()));
+
+    // A subnet mask missing its last byte.
+    let opts: [u8; 13] = [
+        options::MESSAGE_TYPE, 1, 5,
+        options::SUBNET_MASK, 3, 255, 255, 255,
+        options::END, 0, 0, 0, 0,
+    ];
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &opts);
+    assert_eq!(
+        packet.validate_option_lengths(),
+        Err(alloc::vec![options::SUBNET_MASK])
+    );
+}
+
+fn test_server_id() {
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(
+        &mut buffer,
+        &[options::SERVER_IDENTIFIER, 4, 10, 0, 0, 2, options::END],
+    );
+    packet.dhcp4.header.server_addr = [10, 0, 0, 1];
+
+    let mut mode_data = ModeData::default();
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.server_id(), Some(Ipv4Addr::new(10, 0, 0, 2)));
+
+    // Without a server identifier the header address is used.
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::END]);
+    packet.dhcp4.header.server_addr = [10, 0, 0, 1];
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.server_id(), Some(Ipv4Addr::new(10, 0, 0, 1)));
+
+    // Neither the option nor the header names a server.
+    let mut buffer = [0u64; 64];
+    mode_data.reply_packet = build_packet(&mut buffer, &[options::END]);
+    assert_eq!(mode_data.server_id(), None);
+}
+
+fn test_offered_address() {
+    let mut mode_data = ModeData::default();
+    assert_eq!(mode_data.offered_address(), None);
+
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::END]);
+    packet.dhcp4.header.your_addr = [10, 0, 2, 15];
+    mode_data.reply_packet = packet;
+    mode_data.set_state(State::Dhcp4Selecting);
+    assert_eq!(mode_data.offered_address(), Some(Ipv4Addr::new(10, 0, 2, 15)));
+    assert_eq!(mode_data.client_address, [0, 0, 0, 0]);
+}
+
+fn test_domain_name() {
+    let mut buffer = [0u64; 64];
+    let mut opts = [options::DOMAIN_NAME, 11, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, options::END];
+    opts[2..13].copy_from_slice(b"example.com");
+    let packet = build_packet(&mut buffer, &opts);
+
+    let mut mode_data = ModeData::default();
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.domain_name(), Some("example.com"));
+
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::END]);
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.domain_name(), None);
+}
+
+fn test_tftp_server_name() {
+    let mut buffer = [0u64; 64];
+    let mut opts = [0u8; 18];
+    opts[..2].copy_from_slice(&[options::TFTP_SERVER_NAME, 15]);
+    opts[2..17].copy_from_slice(b"tftp.example.io");
+    opts[17] = options::END;
+    let packet = build_packet(&mut buffer, &opts);
+
+    let mut mode_data = ModeData::default();
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.tftp_server_name(), Some("tftp.example.io"));
+
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::END]);
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.tftp_server_name(), None);
+}
+
+fn test_pxe_reply() {
+    let mut mode_data = ModeData::default();
+    assert!(!mode_data.is_pxe_reply());
+
+    let mut buffer = [0u64; 64];
+    let mut opts = [0u8; 12];
+    opts[..2].copy_from_slice(&[options::VENDOR_CLASS_IDENTIFIER, 9]);
+    opts[2..11].copy_from_slice(b"PXEClient");
+    opts[11] = options::END;
+    let packet = build_packet(&mut buffer, &opts);
+    mode_data.reply_packet = packet;
+    assert!(mode_data.is_pxe_reply());
+
+    let mut buffer = [0u64; 64];
+    let mut opts = [0u8; 12];
+    opts[..2].copy_from_slice(&[options::VENDOR_CLASS_IDENTIFIER, 9]);
+    opts[2..11].copy_from_slice(b"MSFT 5.0\0");
+    opts[11] = options::END;
+    let packet = build_packet(&mut buffer, &opts);
+    mode_data.reply_packet = packet;
+    assert!(!mode_data.is_pxe_reply());
+
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::END]);
+    mode_data.reply_packet = packet;
+    assert!(!mode_data.is_pxe_reply());
+}
+
+fn test_ntp_servers() {
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::NTP_SERVERS, 4, 10, 0, 0, 123, options::END]);
+
+    let mut mode_data = ModeData::default();
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.ntp_servers(), [Ipv4Addr::new(10, 0, 0, 123)]);
+
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::END]);
+    mode_data.reply_packet = packet;
+    assert!(mode_data.ntp_servers().is_empty());
+}
+
+fn test_parse_boot_uri() {
+    assert_eq!(parse_boot_uri("http://h/p"), Some((Scheme::Http, "h", 80, "/p")));
+    assert_eq!(parse_boot_uri("https://h:8443/p"), Some((Scheme::Https, "h", 8443, "/p")));
+    assert_eq!(
+        parse_boot_uri("HTTP://192.168.1.10/boot/grubx64.efi"),
+        Some((Scheme::Http, "192.168.1.10", 80, "/boot/grubx64.efi"))
+    );
+    assert_eq!(parse_boot_uri("https://10.0.0.1"), Some((Scheme::Https, "10.0.0.1", 443, "/")));
+
+    assert_eq!(parse_boot_uri("tftp://h/p"), None);
+    assert_eq!(parse_boot_uri("http:///p"), None);
+    assert_eq!(parse_boot_uri("http://h:port/p"), None);
+}
+
+fn test_pad_run() {
+    let mut opts = [options::PAD; 1024];
+    opts[..3].copy_from_slice(&[options::MESSAGE_TYPE, 1, 5]);
+    opts[1000..1006].copy_from_slice(&[options::SERVER_IDENTIFIER, 4, 10, 0, 0, 1]);
+    opts[1006] = options::END;
+    // Bytes after the end option are never read
+    opts[1010..1013].copy_from_slice(&[options::MESSAGE_TYPE, 1, 2]);
+
+    let found: alloc::vec::Vec<_> = OptionIterator::new(&opts, usize::MAX).collect();
+    assert_eq!(
+        found,
+        [
+            (options::MESSAGE_TYPE, &[5u8][..]),
+            (options::SERVER_IDENTIFIER, &[10, 0, 0, 1][..]),
+        ]
+    );
+
+    // An options area of only pads has no options
+    assert_eq!(OptionIterator::new(&[options::PAD; 64], usize::MAX).count(), 0);
+}
+
+fn test_renewing_states() {
+    let mut mode_data = ModeData::default();
+    for &(state, renewing, usable) in &[
+        (State::Dhcp4Init, false, false),
+        (State::Dhcp4Requesting, false, false),
+        (State::Dhcp4Bound, false, true),
+        (State::Dhcp4Renewing, true, true),
+        (State::Dhcp4Rebinding, true, true),
+        (State::Dhcp4Rebooting, false, false),
+    ] {
+        mode_data.set_state(state);
+        assert_eq!(mode_data.is_renewing(), renewing, "{:?}", state);
+        assert_eq!(mode_data.is_bound_or_renewing(), usable, "{:?}", state);
+    }
+}
+
+fn test_configured_states() {
+    let mut mode_data = ModeData::default();
+    assert_eq!(mode_data.state(), Ok(State::Dhcp4Stopped));
+    assert!(!mode_data.is_configured());
+
+    mode_data.set_state(State::Dhcp4Init);
+    assert!(mode_data.is_configured());
+}
+
+fn test_subnet_mask() {
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(
+        &mut buffer,
+        &[options::SUBNET_MASK, 4, 255, 255, 0, 0, options::END],
+    );
+
+    let mut mode_data = ModeData::default();
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.subnet_mask_any(), Some(Ipv4Addr::new(255, 255, 0, 0)));
+
+    // The field takes precedence over the option.
+    mode_data.subnet_mask = [255, 255, 255, 0];
+    assert_eq!(
+        mode_data.subnet_mask_any(),
+        Some(Ipv4Addr::new(255, 255, 255, 0))
+    );
+}
+
+fn test_lease_thresholds() {
+    let mut mode_data = ModeData::default();
+    mode_data.lease_time = 1000;
+
+    let at = |percent: u64| Duration::from_secs(10 * percent);
+    assert!(!mode_data.should_renew(at(49)));
+    assert!(mode_data.should_renew(at(51)));
+    assert!(!mode_data.should_rebind(at(86)));
+    assert!(mode_data.should_rebind(at(88)));
+
+    mode_data.lease_time = INFINITE_LEASE;
+    assert_eq!(mode_data.lease_duration(), None);
+    assert!(!mode_data.should_renew(at(1_000_000)));
+    assert!(!mode_data.should_rebind(at(1_000_000)));
+}
+
+fn test_hops() {
+    let mut header = Header::default();
+    assert_eq!(header.hops(), 0);
+
+    for n in 1..=MAX_HOPS {
+        header.increment_hops().expect_success("Hop count below the limit");
+        assert_eq!(header.hops(), n);
+    }
+    let ret = header.increment_hops();
+    assert_eq!(ret.map_err(|err| err.status()), Err(Status::ABORTED));
+    assert_eq!(header.hops(), MAX_HOPS);
+
+    header.set_hops(3);
+    assert_eq!(header.hops(), 3);
+}
+
+fn test_header_addrs() {
+    let mut header = Header::default();
+    let addr = Ipv4Addr::new(192, 168, 1, 10);
+
+    header.set_client_addr(addr).expect_success("Failed to set ciaddr");
+    header.set_your_addr(addr.succ()).expect_success("Failed to set yiaddr");
+    header.set_server_addr(addr.succ().succ()).expect_success("Failed to set siaddr");
+    header.set_gateway_addr(Ipv4Addr::new(192, 168, 1, 1)).expect_success("Failed to set giaddr");
+    assert_eq!(header.client_addr, [192, 168, 1, 10]);
+    assert_eq!(header.your_addr, [192, 168, 1, 11]);
+    assert_eq!(header.server_addr, [192, 168, 1, 12]);
+    assert_eq!(header.gateway_addr, [192, 168, 1, 1]);
+
+    // Invalid addresses leave the field untouched.
+    let ret = header.set_client_addr(Ipv4Addr::new(255, 255, 255, 255));
+    assert_eq!(ret.map_err(|err| err.status()), Err(Status::INVALID_PARAMETER));
+    let ret = header.set_your_addr(Ipv4Addr::new(224, 0, 0, 1));
+    assert_eq!(ret.map_err(|err| err.status()), Err(Status::INVALID_PARAMETER));
+    assert_eq!(header.client_addr, [192, 168, 1, 10]);
+    assert_eq!(header.your_addr, [192, 168, 1, 11]);
+}
+
+/// Lay out a packet carrying `opts` at the start of `buffer`.
+fn build_packet<'a>(buffer: &'a mut [u64], opts: &[u8]) -> &'a mut Packet {
+    let packet = unsafe { &mut *(buffer.as_mut_ptr() as *mut Packet) };
+    let hdr = size_of::<Header>() + size_of::<u32>();
+    packet.size = (buffer.len() * 8) as u32;
+    packet.length = (hdr + opts.len()) as u32;
+    unsafe { packet.options_mut()[..opts.len()].copy_from_slice(opts) };
+    packet
+}
+
+fn test_ip4_config2_discriminants() {
+    assert_eq!(DataType::InterfaceInfo as usize, 0);
+    assert_eq!(DataType::DnsServer as usize, 4);
+    assert_eq!(Policy::Dhcp as usize, 1);
+}
+
+fn test_oack_tsize() {
+    // OACK acknowledging a block size and the transfer size of a 16 MiB file.
+    let oack = b"\x00\x06blksize\x001468\x00TSIZE\x0016777216\x00";
+    let options = OackOptions::parse(oack).expect("Failed to parse OACK");
+    assert_eq!(options.get("blksize"), Some("1468"));
+    assert_eq!(options.tsize(), Some(16 * 1024 * 1024));
+
+    // A server acknowledging options without the transfer size.
+    let oack = b"\x00\x06blksize\x001468\x00";
+    assert_eq!(OackOptions::parse(oack).and_then(|o| o.tsize()), None);
+
+    // A data packet is not an OACK.
+    assert!(OackOptions::parse(b"\x00\x03\x00\x01data").is_none());
+}
+
+fn test_network_adapters(bt: &BootServices) {
+    let adapters = network_adapters(bt).expect_success("Failed to list network adapters");
+
+    // The runner attaches two network cards.
+    assert!(adapters.len() >= 2, "Missing network adapters");
+
+    for (i, (handle, mac)) in adapters.iter().enumerate() {
+        info!("- Network adapter {}: {:?}", i, mac);
+
+        let snp = bt
+            .handle_protocol::<SimpleNetwork>(*handle)
+            .expect_success("Failed to open simple network protocol");
+        let snp = unsafe { &*snp.get() };
+        assert_eq!(*mac, snp.mode().permanent_address);
+
+        assert!(
+            adapters[..i].iter().all(|(_, other)| other != mac),
+            "Network adapters must have distinct MAC addresses"
+        );
+    }
+}
+
+fn test_policy(ip4_config2: &mut IP4Config2) {
+    let original = ip4_config2
+        .policy()
+        .expect_success("Failed to get IP4 config2 policy");
+
+    for &policy in &[Policy::Static, Policy::Dhcp] {
+        ip4_config2
+            .set_policy(policy)
+            .expect_success("Failed to set IP4 config2 policy");
+        let current = ip4_config2
+            .policy()
+            .expect_success("Failed to get IP4 config2 policy");
+        assert_eq!(current, policy);
+    }
+
+    ip4_config2
+        .set_policy(original)
+        .expect_success("Failed to restore IP4 config2 policy");
+}
+
+/// Reset the driver and acquire a lease of its own, so that a test does not
+/// depend on the state left by the others.
+fn acquire_lease(dhcp4: &mut DHCP4) -> ModeData {
+    dhcp4
+        .configure(ptr::null())
+        .expect_success("Failed to reset DHCP4 configuration");
+    dhcp4
+        .acquire(&ConfigData::default())
+        .expect_success("Failed to acquire DHCP4 lease")
+}
+
+fn test_inform(dhcp4: &mut DHCP4, bt: &BootServices) {
+    // Informing requires the address the client uses.
+    let ret = dhcp4.inform(Ipv4Addr::default(), bt);
+    assert_eq!(
+        ret.map(|_| ()).map_err(|err| err.status()),
+        Err(Status::INVALID_PARAMETER)
+    );
+
+    // Inform about an address known to be valid on the network.
+    let address = Ipv4Addr::from(acquire_lease(dhcp4).client_address);
+    assert!(!address.is_unspecified());
+
+    match dhcp4.inform(address, bt) {
+        Ok(mode_data) => {
+            let mode_data = mode_data.log();
+            let ack = mode_data.reply().expect("No DHCPACK in DHCP4 mode data");
+            assert_eq!(
+                ack.option(options::MESSAGE_TYPE),
+                Some(&[options::DHCPACK][..])
+            );
+            bt.free_pool(mode_data.reply_packet as *mut u8)
+                .expect_success("Failed to free DHCPACK copy");
+        }
+        // QEMU's user network only answers discoveries and requests.
+        Err(err) => assert_eq!(err.status(), Status::NO_RESPONSE),
+    }
+
+    // Either way, no lease is obtained.
+    let mode_data = dhcp4
+        .mode_data()
+        .expect_success("Failed to get DHCP4 mode data");
+    assert_eq!(mode_data.state(), Ok(State::Dhcp4InitReboot));
+
+    dhcp4
+        .configure(ptr::null())
+        .expect_success("Failed to reset DHCP4 configuration");
+}
+
+fn test_update_callback(dhcp4: &mut DHCP4) {
+    // The lease is acquired without a callback.
+    acquire_lease(dhcp4);
+    let mut config = CallbackConfig::new(ConfigData::default());
+    dhcp4
+        .update_callback(&mut config, |_, _, _| CallbackAction::Continue)
+        .expect_success("Failed to set DHCP4 callback");
+
+    let mode_data = dhcp4.mode_data().expect_success("Failed to get DHCP4 mode data");
+    assert_eq!(mode_data.state(), Ok(State::Dhcp4Bound));
+    assert_eq!(mode_data.config_data.callback_context, config.config().callback_context);
+
+    dhcp4
+        .update_callback(&mut config, |_, _, _| CallbackAction::Abort)
+        .expect_success("Failed to swap DHCP4 callback");
+
+    // Only the configuration the driver runs with can be updated.
+    let mut other = CallbackConfig::new(ConfigData::default());
+    other.set_callback(|_, _, _| CallbackAction::Continue);
+    let ret = dhcp4.update_callback(&mut other, |_, _, _| CallbackAction::Continue);
+    assert_eq!(ret.map_err(|err| err.status()), Err(Status::INVALID_PARAMETER));
+
+    // The driver must not call back into the closure once it is dropped
+    dhcp4
+        .configure(&ConfigData::default())
+        .expect_success("Failed to reset DHCP4 configuration");
+}
+
+fn test_capture_negotiation(dhcp4: &mut DHCP4) {
+    dhcp4
+        .configure(ptr::null())
+        .expect_success("Failed to reset DHCP4 configuration");
+    let log = dhcp4
+        .capture_negotiation(&ConfigData::default())
+        .expect_success("Failed to capture DHCP negotiation");
+
+    let expected = [
+        Event::Dhcp4SendDiscover,
+        Event::Dhcp4RcvdOffer,
+        Event::Dhcp4SelectOffer,
+        Event::Dhcp4SendRequest,
+        Event::Dhcp4RcvdAck,
+        Event::Dhcp4BoundCompleted,
+    ];
+    let mut events = log.events();
+    for event in &expected {
+        assert!(
+            events.any(|e| e == *event),
+            "Missing {:?} in DHCP negotiation",
+            event
+        );
+    }
+
+    let ack = log
+        .entries
+        .iter()
+        .find(|entry| entry.event == Event::Dhcp4RcvdAck)
+        .unwrap();
+    assert!(ack.packet.is_some());
+}
+
+fn test_options_bounded() {
+    // 1000 empty options followed by the end option.
+    let mut opts = [0u8; 2001];
+    for option in opts[..2000].chunks_exact_mut(2) {
+        option.copy_from_slice(&[200, 0]);
+    }
+    opts[2000] = options::END;
+
+    let mut buffer = [0u64; 320];
+    let packet = build_packet(&mut buffer, &opts);
+    assert_eq!(packet.options().count(), 1000);
+    assert_eq!(packet.options_bounded(32).count(), 32);
+}
+
+fn test_state_round_trip() {
+    for &state in &[
+        State::Dhcp4Stopped,
+        State::Dhcp4Init,
+        State::Dhcp4Selecting,
+        State::Dhcp4Requesting,
+        State::Dhcp4Bound,
+        State::Dhcp4Renewing,
+        State::Dhcp4Rebinding,
+        State::Dhcp4InitReboot,
+        State::Dhcp4Rebooting,
+    ] {
+        let raw = u32::from(state);
+        assert_eq!(State::try_from(raw), Ok(state));
+
+        let mut mode_data = ModeData::default();
+        mode_data.set_state(state);
+        assert_eq!(mode_data.state(), Ok(state));
+    }
+    assert!(State::try_from(0x9u32).is_err());
+}
+
+fn test_invalid_events() {
+    assert_eq!(Event::try_from(0x01u32), Ok(Event::Dhcp4SendDiscover));
+    assert_eq!(Event::try_from(0x0cu32), Ok(Event::Dhcp4Fail));
+    assert!(Event::try_from(0x00u32).is_err());
+    assert!(Event::try_from(0x0du32).is_err());
+}
+
+fn test_magic_cookie() {
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::END]);
+    assert!(!packet.dhcp4.magic_valid());
+
+    packet.dhcp4.set_magic();
+    assert!(packet.dhcp4.magic_valid());
+    assert_eq!(packet.dhcp4.magik.to_ne_bytes(), [0x63, 0x82, 0x53, 0x63]);
+}
+
+fn test_boot_filename() {
+    let mut mode_data = ModeData::default();
+    assert_eq!(mode_data.boot_filename(), None);
+
+    // Both the option and the header field, the option wins.
+    let mut opts = [options::END; 11];
+    opts[..2].copy_from_slice(&[options::BOOTFILE_NAME, 8]);
+    opts[2..10].copy_from_slice(b"boot.efi");
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &opts);
+    packet.dhcp4.header.bootfile_name[..6].copy_from_slice(b"pxelnx");
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.boot_filename(), Some("boot.efi"));
+
+    // A BOOTP style reply naming the file in the header only.
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::END]);
+    packet.dhcp4.header.bootfile_name[..6].copy_from_slice(b"pxelnx");
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.boot_filename(), Some("pxelnx"));
+
+    // Neither names a file.
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::END]);
+    mode_data.reply_packet = packet;
+    assert_eq!(mode_data.boot_filename(), None);
+}
+
+/// Take the interface down to the stopped state.
+fn take_down(snp: &mut SimpleNetwork) {
+    if snp.mode().state == NetworkState::INITIALIZED {
+        snp.shutdown()
+            .expect_success("Failed to shut down network interface");
+    }
+    if snp.mode().state == NetworkState::STARTED {
+        snp.stop().expect_success("Failed to stop network interface");
+    }
+    assert_eq!(snp.mode().state, NetworkState::STOPPED);
+}
+
+fn test_bring_up(snp: &mut SimpleNetwork) {
+    // The network stack usually starts the interface before the test runs.
+    let original = snp.mode().state;
+    take_down(snp);
+
+    snp.bring_up()
+        .expect_success("Failed to bring up network interface");
+    assert_eq!(snp.mode().state, NetworkState::INITIALIZED);
+
+    let ret = snp.bring_up();
+    assert_eq!(
+        ret.map_err(|err| err.status()),
+        Err(Status::ALREADY_STARTED)
+    );
+
+    // Leave the interface as it was found.
+    if original == NetworkState::STOPPED {
+        take_down(snp);
+    } else if original == NetworkState::STARTED {
+        snp.shutdown()
+            .expect_success("Failed to shut down network interface");
+    }
+    assert_eq!(snp.mode().state, original);
+}
+
+fn test_config_builder() {
+    let config = ConfigDataBuilder::new().build();
+    assert_eq!(config.discovery_try_count, 0);
+    assert!(config.discovery_try_timeout.is_null());
+    assert_eq!(config.request_try_count, 0);
+    assert!(config.request_timeout.is_null());
+
+    let discovery = [1, 2, 4, 8];
+    let request = [2, 4];
+    let config = ConfigDataBuilder::new()
+        .discovery_timeouts(&discovery)
+        .request_timeouts(&request)
+        .build();
+    assert_eq!(config.discovery_try_count as usize, discovery.len());
+    assert_eq!(config.discovery_try_timeout, discovery.as_ptr());
+    assert_eq!(config.request_try_count as usize, request.len());
+    assert_eq!(config.request_timeout, request.as_ptr());
+}
+
+fn test_callback_trampoline() {
+    let mut calls = 0;
+    let mut buffer = [0u64; 64];
+    let replacement: *mut Packet = build_packet(&mut buffer, &[options::END]);
+
+    let mut config = CallbackConfig::new(ConfigData::default());
+    config.set_callback(|_, event, _| {
+        calls += 1;
+        match event {
+            Event::Dhcp4SendDiscover => CallbackAction::Replace(replacement),
+            _ => CallbackAction::Continue,
+        }
+    });
+    let callback = config.config().dhcp4_callback.expect("No callback set");
+    let context = config.config().callback_context;
+    let this = ptr::null_mut();
+    let packet = ptr::null();
+    let no_new = ptr::null_mut();
+
+    // Unknown states and events are let through without calling the closure.
+    assert_eq!(callback(this, context, 0x9, 0x01, packet, no_new), Status::SUCCESS);
+    assert_eq!(callback(this, context, 0x2, 0x0d, packet, no_new), Status::SUCCESS);
+
+    assert_eq!(callback(this, context, 0x2, 0x02, packet, no_new), Status::SUCCESS);
+
+    // A new packet is only handed over where the driver accepts one.
+    let mut new_packet = ptr::null_mut();
+    assert_eq!(callback(this, context, 0x1, 0x01, packet, &mut new_packet), Status::SUCCESS);
+    assert_eq!(new_packet, replacement);
+    let ret = callback(this, context, 0x1, 0x01, packet, no_new);
+    assert_eq!(ret, Status::INVALID_PARAMETER);
+
+    drop(config);
+    assert_eq!(calls, 3);
+}
+
+fn test_options_write() {
+    let mut buffer = [0u64; 64];
+    let packet = build_packet(&mut buffer, &[options::ROUTER, 4, 10, 0, 0, 1, options::END]);
+
+    unsafe { packet.options_mut()[5] = 254 };
+    assert_eq!(packet.options_bytes(), &[options::ROUTER, 4, 10, 0, 0, 254, options::END]);
+}
+
+fn test_release_to_server(dhcp4: &mut DHCP4, bt: &BootServices) {
+    acquire_lease(dhcp4);
+
+    dhcp4
+        .release_to_server(bt)
+        .expect_success("Failed to release DHCP4 lease");
+    let mode_data = dhcp4.mode_data().expect_success("Failed to get DHCP4 mode data");
+    assert_eq!(mode_data.state(), Ok(State::Dhcp4Stopped));
+
+    let ret = dhcp4.release_to_server(bt);
+    assert_eq!(ret.map_err(|err| err.status()), Err(Status::NOT_STARTED));
+
+    // Leave the network configured for the tests that follow.
+    dhcp4
+        .acquire(&ConfigData::default())
+        .expect_success("Failed to acquire DHCP4 lease");
+}
+
+/// Size of the file served over TFTP by the runner, see `build.py`.
+const TFTP_FILE_SIZE: u64 = 12345;
+
+fn test_get_file_size(mtftp4: &mut Mtftp4, bt: &BootServices) {
+    // The gateway of QEMU's user network serves the TFTP directory.
+    let config = Mtftp4ConfigData {
+        try_count: 1,
+        timeout_value: 1,
+        ..Mtftp4ConfigData::new(Ipv4Addr::new(10, 0, 2, 2))
+    };
+    mtftp4
+        .configure(&config)
+        .expect_success("Failed to configure MTFTP4");
+
+    let size = mtftp4
+        .get_file_size("tsize.bin", bt)
+        .expect_success("Failed to get TFTP transfer size");
+    assert_eq!(size, TFTP_FILE_SIZE);
+
+    // Errors sent by the server are not taken for a missing transfer size.
+    let ret = mtftp4.get_file_size("missing.bin", bt);
+    assert_eq!(ret.map_err(|err| err.status()), Err(Status::TFTP_ERROR));
+
+    mtftp4
+        .configure(ptr::null())
+        .expect_success("Failed to reset MTFTP4 configuration");
+}